@@ -0,0 +1,80 @@
+use crate::git::Git;
+use crate::report::Outcome;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+pub const DEFAULT_JOBS: usize = 8;
+
+/// Runs `f` over every item using at most `jobs` worker threads.
+/// Results come back in the same order as `items`, regardless of which worker finished first.
+pub fn run_parallel<T, R, F>(items: &[T], jobs: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+    let workers = jobs.clamp(1, items.len().max(1));
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                let result = f(item);
+                results.lock().expect("results lock poisoned")[index] = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .expect("results lock poisoned")
+        .into_iter()
+        .map(|result| result.expect("every item is processed by a worker"))
+        .collect()
+}
+
+/// Pulls one clone, refusing to touch a working tree that has local changes
+/// unless `autostash` is set. An in-progress rebase or merge is never touched.
+pub fn pull_guarded(git: &impl Git, autostash: bool) -> Outcome {
+    let status = match git.worktree_status() {
+        Ok(status) => status,
+        Err(e) => return Outcome::Failed(format!("{:#}", e)),
+    };
+    if status.is_clean() {
+        return match git.pull() {
+            Ok(pulled) => pulled.into(),
+            Err(e) => Outcome::Failed(format!("{:#}", e)),
+        };
+    }
+    if !autostash || status.operation_in_progress() {
+        return Outcome::DirtySkipped(status.describe());
+    }
+
+    if let Err(e) = git.stash() {
+        return Outcome::Failed(format!("{:#}", e));
+    }
+    let pulled = git.pull();
+    if let Err(e) = git.stash_pop() {
+        let what_happened = match &pulled {
+            Ok(_) => "pulled, but restoring the autostash failed".to_string(),
+            Err(pull_error) => format!(
+                "pull failed ({:#}), and restoring the autostash failed too",
+                pull_error
+            ),
+        };
+        return Outcome::Failed(format!(
+            "{}; your changes are still in `git stash list`\n{:#}",
+            what_happened, e
+        ));
+    }
+    match pulled {
+        Ok(pulled) => pulled.into(),
+        Err(e) => Outcome::Failed(format!("{:#}", e)),
+    }
+}
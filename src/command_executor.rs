@@ -1,74 +1,130 @@
+use std::fmt;
+use std::path::Path;
 use std::process::Command;
+use std::time::{Duration, Instant};
 
-pub trait CommandExecutor {
-    fn run_command(&self, command: &str, args: &str) -> String;
-    fn command_success(&self, command: &str, args: &str) -> bool;
-    fn run_explicit_command(&self, command: &str, args: Vec<&str>) -> String;
+/// Everything a caller might want to know about a finished command.
+/// Running a command never panics; a command that could not be started
+/// comes back with no exit status and the spawn error as stderr.
+#[derive(Debug, Clone)]
+pub struct CommandOutput {
+    pub command: String,
+    pub status: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration: Duration,
 }
-pub struct RealCommandExecutor;
 
-impl CommandExecutor for RealCommandExecutor {
-    fn run_command(&self, command: &str, args: &str) -> String {
-        let output = Command::new(command)
-            .args(args.split(" "))
-            .output()
-            .expect("failed to call command");
+impl CommandOutput {
+    pub fn success(&self) -> bool {
+        self.status == Some(0)
+    }
 
-        match output.status.success() {
-            true => Ok(String::from_utf8_lossy(&output.stdout).to_string()),
-            false => Err(&output.stderr),
+    /// Trimmed stdout on success, otherwise an error carrying stderr.
+    pub fn into_result(self) -> Result<String, CommandError> {
+        if self.success() {
+            Ok(self.stdout.trim().to_string())
+        } else {
+            Err(CommandError { output: self })
         }
-        .expect(&format!("Failed to execute command: {} {}", command, args))
     }
+}
+
+#[derive(Debug)]
+pub struct CommandError {
+    pub output: CommandOutput,
+}
 
-    fn command_success(&self, command: &str, args: &str) -> bool {
-        Command::new(command)
-            .args(args.split(" "))
-            .output()
-            .expect("failed to call command")
-            .status
-            .success()
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let output = &self.output;
+        let status = match output.status {
+            Some(code) => format!("exit {}", code),
+            None => "not run".to_string(),
+        };
+        write!(
+            f,
+            "`{}` failed ({}, {:.1}s)",
+            output.command,
+            status,
+            output.duration.as_secs_f32()
+        )?;
+        if !output.stderr.trim().is_empty() {
+            write!(f, ": {}", output.stderr.trim())?;
+        }
+        Ok(())
     }
+}
 
-    fn run_explicit_command(&self, command: &str, args: Vec<&str>) -> String {
-        let output = Command::new(command)
-            .args(&args)
-            .output()
-            .expect("failed to call command");
+impl std::error::Error for CommandError {}
 
-        match output.status.success() {
-            true => Ok(String::from_utf8_lossy(&output.stdout).to_string()),
-            false => Err(&output.stderr),
-        }
-        .expect(&format!(
-            "Failed to execute command: {} {:?}",
-            command, args
-        ))
+pub trait CommandExecutor: Sync {
+    /// Runs `command` with `dir` as its working directory, or in the current directory when `dir` is `None`.
+    fn run_command_in(&self, dir: Option<&Path>, command: &str, args: &[&str]) -> CommandOutput;
+
+    fn run_command(&self, command: &str, args: &[&str]) -> CommandOutput {
+        self.run_command_in(None, command, args)
     }
 }
+pub struct RealCommandExecutor;
 
-pub struct DebugCommandExecutor;
+impl CommandExecutor for RealCommandExecutor {
+    fn run_command_in(&self, dir: Option<&Path>, command: &str, args: &[&str]) -> CommandOutput {
+        let started = Instant::now();
+        let mut process = Command::new(command);
+        process.args(args);
+        if let Some(dir) = dir {
+            process.current_dir(dir);
+        }
+        let output = process.output();
+        let command = match dir {
+            Some(dir) => format!("{} {} (in {})", command, args.join(" "), dir.display()),
+            None => format!("{} {}", command, args.join(" ")),
+        };
 
-impl CommandExecutor for DebugCommandExecutor {
-    fn run_command(&self, command: &str, args: &str) -> String {
-        println!("DEBUG: Simulating execution of `{} {}`", command, args);
-        if command == "git" {
-            if args == "remote get-url origin" {
-                return "git@github.com:meatlore/git-remote.git".parse().unwrap();
-            }
+        match output {
+            Ok(output) => CommandOutput {
+                command,
+                status: output.status.code(),
+                stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+                stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+                duration: started.elapsed(),
+            },
+            Err(e) => CommandOutput {
+                command,
+                status: None,
+                stdout: String::new(),
+                stderr: format!("failed to call command: {}", e),
+                duration: started.elapsed(),
+            },
         }
-        "mocked output".to_string()
     }
+}
 
-    fn command_success(&self, command: &str, args: &str) -> bool {
-        println!(
-            "DEBUG: Pretending `{}` with args `{}` succeeded",
-            command, args
-        );
-        true // Always return success during debug mode
-    }
+pub struct DebugCommandExecutor;
 
-    fn run_explicit_command(&self, _command: &str, _args: Vec<&str>) -> String {
-        todo!()
+impl CommandExecutor for DebugCommandExecutor {
+    fn run_command_in(&self, dir: Option<&Path>, command: &str, args: &[&str]) -> CommandOutput {
+        let command = format!("{} {}", command, args.join(" "));
+        match dir {
+            Some(dir) => println!(
+                "DEBUG: Simulating execution of `{}` in {}",
+                command,
+                dir.display()
+            ),
+            None => println!("DEBUG: Simulating execution of `{}`", command),
+        }
+        let stdout = if command == "git remote get-url origin" {
+            "git@github.com:meatlore/git-remote.git"
+        } else {
+            "mocked output"
+        };
+        CommandOutput {
+            command,
+            status: Some(0), // Always return success during debug mode
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            duration: Duration::ZERO,
+        }
     }
 }
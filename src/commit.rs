@@ -0,0 +1,562 @@
+use crate::bulk;
+use crate::config::{GitsConfig, RealGitsConfig, RemotesConfig};
+use crate::git::{
+    is_cloned, ChangedFile, Git, HostKind, Integration, PushOutcome, RealGit, RemoteProject,
+};
+use crate::list::open_url;
+use crate::merge_request::{open_merge_request, MergeRequestOptions};
+use crate::report::{Outcome, Report};
+use crate::watch::WatchSet;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use regex::Regex;
+use reqwest::Url;
+use skim::options::SkimOptionsBuilder;
+use skim::prelude::*;
+use skim::{Skim, SkimItem, SkimItemReceiver, SkimItemSender};
+use std::borrow::Cow;
+use std::sync::Arc;
+
+/// What `commit` stages before committing. Without any of them only what is
+/// already staged gets committed.
+#[derive(Debug, Clone, Default)]
+pub struct Staging {
+    pub all: bool,
+    pub paths: Vec<String>,
+    pub pick: bool,
+}
+
+/// Stages what `staging` asks for and fails when nothing ends up staged.
+pub fn stage(git: &impl Git, staging: &Staging) -> Result<()> {
+    if staging.all {
+        git.stage_all()?;
+    }
+    if !staging.paths.is_empty() {
+        git.stage(&staging.paths)?;
+    }
+    if staging.pick {
+        let picked = pick_files(git)?;
+        if picked.is_empty() {
+            bail!("no files picked, nothing committed");
+        }
+        git.stage(&picked)?;
+    }
+    if git.worktree_status()?.staged == 0 {
+        bail!("nothing staged to commit: `git add` what you want, or use --all, --path or --pick");
+    }
+    Ok(())
+}
+
+/// A changed file in the picker, previewed with its diff.
+struct FileItem {
+    file: ChangedFile,
+    diff: String,
+    /// skim tells ticked items apart by index
+    index: usize,
+}
+
+impl SkimItem for FileItem {
+    fn text(&self) -> Cow<'_, str> {
+        Cow::Owned(format!("{} {}", self.file.status, self.file.path))
+    }
+
+    fn get_index(&self) -> usize {
+        self.index
+    }
+
+    fn preview(&self, _context: PreviewContext) -> ItemPreview {
+        ItemPreview::Text(self.diff.clone())
+    }
+}
+
+/// Lets the user tick the changed files to stage and returns them as pathspecs.
+fn pick_files(git: &impl Git) -> Result<Vec<String>> {
+    let files = git.changed_files(&[])?;
+    if files.is_empty() {
+        bail!("nothing changed, nothing to commit");
+    }
+
+    let options = SkimOptionsBuilder::default()
+        .prompt("Stage > ".to_string())
+        .height("50%".to_string())
+        .multi(true) // tab ticks files
+        .preview(Some(String::new())) // items render their own preview
+        .preview_window("right:60%".to_string())
+        .build()?;
+
+    let (tx, rx): (SkimItemSender, SkimItemReceiver) = skim::prelude::unbounded();
+    for (index, file) in files.into_iter().enumerate() {
+        let diff = if file.status == "??" {
+            "new file, not tracked yet".to_string()
+        } else {
+            git.diff(&file.path)
+                .unwrap_or_else(|e| format!("no diff: {:#}", e))
+        };
+        if tx.send(Arc::new(FileItem { file, diff, index })).is_err() {
+            break;
+        }
+    }
+    drop(tx);
+
+    let Some(out) = Skim::run_with(&options, Some(rx)) else {
+        bail!("no skim output");
+    };
+    if out.is_abort {
+        return Ok(Vec::new());
+    }
+    Ok(out
+        .selected_items
+        .iter()
+        .filter_map(|item| (**item).as_any().downcast_ref::<FileItem>())
+        // porcelain paths are relative to the top of the worktree, and may contain glob characters
+        .map(|item| format!(":(top,literal){}", item.file.path))
+        .collect())
+}
+
+/// The conventional commit types, see <https://www.conventionalcommits.org>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CommitType {
+    Build,
+    Chore,
+    Ci,
+    Docs,
+    Feat,
+    Fix,
+    Perf,
+    Refactor,
+    Revert,
+    Style,
+    Test,
+}
+
+impl CommitType {
+    fn as_str(self) -> &'static str {
+        match self {
+            CommitType::Build => "build",
+            CommitType::Chore => "chore",
+            CommitType::Ci => "ci",
+            CommitType::Docs => "docs",
+            CommitType::Feat => "feat",
+            CommitType::Fix => "fix",
+            CommitType::Perf => "perf",
+            CommitType::Refactor => "refactor",
+            CommitType::Revert => "revert",
+            CommitType::Style => "style",
+            CommitType::Test => "test",
+        }
+    }
+}
+
+/// How `commit` turns the words on the command line into a commit message.
+#[derive(Debug, Clone, Default)]
+pub struct MessageOptions {
+    pub commit_type: Option<CommitType>,
+    pub scope: Option<String>,
+    /// the remote wants conventional commits, so a message without --type must already be one
+    pub conventional: bool,
+}
+
+/// Builds the commit message. Conventional messages get the ticket from the
+/// branch name (e.g. `feature/ABC-123-login` or `42-fix-typo`) as a `Refs:` footer;
+/// `branch` is only asked for then, and is None on a detached HEAD.
+pub fn build_message(
+    summary: &str,
+    options: &MessageOptions,
+    branch: impl FnOnce() -> Option<String>,
+) -> Result<String> {
+    let summary = summary.trim();
+    if summary.is_empty() {
+        bail!("a commit message is required, e.g. `gits commit fix the login redirect`");
+    }
+    let mut message = match options.commit_type {
+        Some(commit_type) => match &options.scope {
+            Some(scope) => format!("{}({}): {}", commit_type.as_str(), scope, summary),
+            None => format!("{}: {}", commit_type.as_str(), summary),
+        },
+        None if options.conventional => {
+            if !is_conventional(summary) {
+                bail!(
+                    "\"{}\" is not a conventional commit message (`type(scope): summary`), \
+                     write one or pass --type and --scope",
+                    summary
+                );
+            }
+            summary.to_string()
+        }
+        None => return Ok(summary.to_string()),
+    };
+    if let Some(ticket) = branch().and_then(|branch| ticket_from_branch(&branch)) {
+        if !message.contains(&ticket) {
+            message.push_str(&format!("\n\nRefs: {}", ticket));
+        }
+    }
+    Ok(message)
+}
+
+fn is_conventional(message: &str) -> bool {
+    let types = CommitType::value_variants()
+        .iter()
+        .map(|commit_type| commit_type.as_str())
+        .collect::<Vec<_>>()
+        .join("|");
+    Regex::new(&format!(r"^({})(\([\w./-]+\))?!?: \S", types))
+        .expect("valid conventional commit pattern")
+        .is_match(message)
+}
+
+/// A Jira-style key anywhere in the branch name, or a leading issue number.
+fn ticket_from_branch(branch: &str) -> Option<String> {
+    let jira = Regex::new(r"\b([A-Z][A-Z0-9]+-\d+)\b").expect("valid ticket pattern");
+    if let Some(captures) = jira.captures(branch) {
+        return Some(captures[1].to_string());
+    }
+    let issue = Regex::new(r"^(?:[\w.-]+/)?(\d+)[-_]").expect("valid issue pattern");
+    issue
+        .captures(branch)
+        .map(|captures| format!("#{}", &captures[1]))
+}
+
+/// One change rolled out to many repos by `commit --repos`.
+#[derive(Debug, Clone)]
+pub struct BulkCommit {
+    /// which inventoried clones to look at, written like watch entries
+    pub repos: Vec<String>,
+    /// created in every repo that gets a commit
+    pub branch: String,
+    pub summary: String,
+    pub message: MessageOptions,
+    /// only stage what matches these, instead of every change
+    pub paths: Vec<String>,
+    /// overrides each remote's `integrate`
+    pub integration: Option<Integration>,
+    pub push: bool,
+    /// open the page that creates a merge request for every pushed branch
+    pub open_merge_requests: bool,
+    /// open a merge request through the API for every pushed branch
+    pub merge_request: Option<MergeRequestOptions>,
+    pub jobs: usize,
+}
+
+/// Commits and pushes the same change on a new branch in every matching clone
+/// that has one, and reports what happened in each.
+pub async fn commit_repos(
+    git: &RealGit<'_>,
+    config: &RealGitsConfig,
+    bulk: &BulkCommit,
+) -> Result<Report> {
+    let remotes = config.get_remotes_config()?;
+    let filter = WatchSet::filter(&bulk.repos);
+    let projects = config
+        .get_projects()?
+        .into_iter()
+        .filter(|project| filter.matches(&project.repo))
+        .filter(|project| is_cloned(&remotes.repo_path(&project.repo)))
+        .collect::<Vec<_>>();
+    if projects.is_empty() {
+        bail!(
+            "no cloned repo in the inventory matches {}",
+            bulk.repos.join(", ")
+        );
+    }
+
+    let results = bulk::run_parallel(&projects, bulk.jobs, |project| {
+        commit_repo(git, &remotes, project, bulk)
+            .unwrap_or_else(|e| (Outcome::Failed(format!("{:#}", e)), None))
+    });
+
+    let mut report = Report::new();
+    // merge requests are opened one after the other, once every push is done
+    for (project, (mut outcome, pushed_for)) in projects.iter().zip(results) {
+        if let Some(trunk) = pushed_for {
+            let host_kind = remotes
+                .remotes
+                .get(&project.repo.host)
+                .map_or(HostKind::GitLab, |settings| settings.host_kind);
+            if bulk.open_merge_requests {
+                let web_url = remotes.web_url(&project.repo, &project.metadata);
+                match new_merge_request_url(&web_url, host_kind, &bulk.branch, &trunk) {
+                    Ok(url) => {
+                        if let Err(e) = open_url(&url) {
+                            println!("could not open {}: {:#}", url, e);
+                        }
+                    }
+                    Err(e) => println!("no merge request page for {}: {:#}", bulk.branch, e),
+                }
+            }
+            if let Some(options) = &bulk.merge_request {
+                let git = git
+                    .at(remotes.repo_path(&project.repo))
+                    .with_trunk_hint(remotes.trunk_hint(&project.repo, &project.metadata));
+                outcome = match open_merge_request(config, &project.repo, &git, options).await {
+                    Ok(Some(url)) => Outcome::Committed(format!("pushed {}, {}", bulk.branch, url)),
+                    Ok(None) => Outcome::Committed(format!("pushed {}", bulk.branch)),
+                    Err(e) => Outcome::Failed(format!(
+                        "pushed {}, but the merge request failed: {:#}",
+                        bulk.branch, e
+                    )),
+                };
+            }
+        }
+        report.record_repo(&project.repo, outcome);
+    }
+    Ok(report)
+}
+
+/// The outcome for one repo, plus the trunk its pushed branch should be merged into.
+fn commit_repo(
+    git: &RealGit,
+    remotes: &RemotesConfig,
+    project: &RemoteProject,
+    bulk: &BulkCommit,
+) -> Result<(Outcome, Option<String>)> {
+    let repo = &project.repo;
+    let settings = remotes.remotes.get(&repo.host);
+    let git = git
+        .at(remotes.repo_path(repo))
+        .with_trunk_hint(remotes.trunk_hint(repo, &project.metadata));
+
+    let status = git.worktree_status()?;
+    if status.operation_in_progress() {
+        return Ok((Outcome::Skipped(status.describe()), None));
+    }
+    if status.is_clean() {
+        return Ok((Outcome::Skipped("no changes".to_string()), None));
+    }
+    // branching off anything but trunk would drag unrelated commits into the merge request
+    let trunk = git.trunk()?;
+    let current = git.current_branch()?;
+    if current != trunk {
+        let reason = format!("on {}, not {}", current, trunk);
+        return Ok((Outcome::Skipped(reason), None));
+    }
+
+    let options = MessageOptions {
+        conventional: settings.is_some_and(|settings| settings.conventional_commits),
+        ..bulk.message.clone()
+    };
+    let message = build_message(&bulk.summary, &options, || Some(bulk.branch.clone()))?;
+    // the branch comes first, so a name that is taken fails before anything is staged
+    git.create_branch(&bulk.branch)?;
+    // nothing is committed on the new branch until `git commit` succeeds, so it goes again
+    let back_to_trunk = || match git.switch_branch(&trunk) {
+        Ok(()) => {
+            let _ = git.delete_branch(&bulk.branch);
+            format!("back on {}", trunk)
+        }
+        Err(_) => format!("left on {}", bulk.branch),
+    };
+    match stage_changes(&git, &bulk.paths) {
+        Ok(true) => {}
+        Ok(false) => {
+            back_to_trunk();
+            let reason = format!("nothing matches {}", bulk.paths.join(" "));
+            return Ok((Outcome::Skipped(reason), None));
+        }
+        Err(e) => return Err(e.context(format!("staging failed, {}", back_to_trunk()))),
+    }
+    let integration = bulk
+        .integration
+        .or(settings.map(|settings| settings.integrate))
+        .unwrap_or_default();
+    if let Err(e) = git.commit(&message, integration) {
+        return Err(e.context(format!("commit failed, {}", back_to_trunk())));
+    }
+
+    if !bulk.push {
+        let detail = format!("on {}, not pushed", bulk.branch);
+        return Ok((Outcome::Committed(detail), None));
+    }
+    let pushed = git.push(false);
+    if !matches!(pushed, Ok(PushOutcome::Pushed(_))) {
+        // the commit stays on the branch, only the checkout goes back
+        let left = match git.switch_branch(&trunk) {
+            Ok(()) => format!("back on {}", trunk),
+            Err(_) => "still on it".to_string(),
+        };
+        let failure = match pushed {
+            Err(e) => format!("the push failed: {:#}", e),
+            _ => "origin rejected the push".to_string(),
+        };
+        bail!("committed on {}, {}; {}", bulk.branch, left, failure);
+    }
+    let detail = format!("pushed {}", bulk.branch);
+    Ok((Outcome::Committed(detail), Some(trunk)))
+}
+
+/// Stages `paths`, or every change when there are none. Returns whether anything is staged.
+fn stage_changes(git: &impl Git, paths: &[String]) -> Result<bool> {
+    if paths.is_empty() {
+        git.stage_all()?;
+    } else {
+        // a path the shared change did not touch in this repo is no failure, `git add` would refuse it
+        let mut touched = Vec::new();
+        for path in paths {
+            if !git.changed_files(std::slice::from_ref(path))?.is_empty() {
+                touched.push(path.clone());
+            }
+        }
+        if touched.is_empty() {
+            return Ok(false);
+        }
+        git.stage(&touched)?;
+    }
+    Ok(git.worktree_status()?.staged > 0)
+}
+
+/// The page that creates a merge request (pull request on GitHub) from `branch` into `trunk`.
+fn new_merge_request_url(
+    web_url: &str,
+    host_kind: HostKind,
+    branch: &str,
+    trunk: &str,
+) -> Result<String> {
+    let mut url = Url::parse(web_url).with_context(|| format!("parsing {}", web_url))?;
+    {
+        let mut segments = url
+            .path_segments_mut()
+            .map_err(|_| anyhow!("{} cannot have a path", web_url))?;
+        segments.pop_if_empty();
+        match host_kind {
+            HostKind::GitLab => {
+                segments.extend(["-", "merge_requests", "new"]);
+            }
+            HostKind::GitHub => {
+                // branch names keep their slashes, everything else that means something in a url is escaped
+                let range = format!("{}...{}", trunk, branch);
+                segments.push("compare").extend(range.split('/'));
+            }
+        }
+    }
+    match host_kind {
+        HostKind::GitLab => url
+            .query_pairs_mut()
+            .append_pair("merge_request[source_branch]", branch)
+            .append_pair("merge_request[target_branch]", trunk),
+        HostKind::GitHub => url.query_pairs_mut().append_pair("expand", "1"),
+    };
+    Ok(url.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn on(branch: &str) -> impl FnOnce() -> Option<String> + '_ {
+        move || Some(branch.to_string())
+    }
+
+    fn typed(commit_type: CommitType, scope: Option<&str>) -> MessageOptions {
+        MessageOptions {
+            commit_type: Some(commit_type),
+            scope: scope.map(str::to_string),
+            conventional: false,
+        }
+    }
+
+    #[test]
+    fn plain_messages_are_left_alone() {
+        let message = build_message(
+            "  fix the login redirect ",
+            &MessageOptions::default(),
+            || panic!("the branch is only needed for conventional messages"),
+        );
+        assert_eq!(message.unwrap(), "fix the login redirect");
+    }
+
+    #[test]
+    fn refuses_an_empty_message() {
+        assert!(build_message("  ", &MessageOptions::default(), || None).is_err());
+    }
+
+    #[test]
+    fn prefixes_type_and_scope() {
+        let message = build_message("login redirect", &typed(CommitType::Fix, None), on("main"));
+        assert_eq!(message.unwrap(), "fix: login redirect");
+
+        let options = typed(CommitType::Feat, Some("auth"));
+        let message = build_message("sso", &options, on("main"));
+        assert_eq!(message.unwrap(), "feat(auth): sso");
+    }
+
+    #[test]
+    fn adds_the_ticket_from_the_branch_as_a_footer() {
+        let options = typed(CommitType::Feat, None);
+        let message = build_message("login", &options, on("feature/ABC-123-login"));
+        assert_eq!(message.unwrap(), "feat: login\n\nRefs: ABC-123");
+
+        let message = build_message("typo", &options, on("42-fix-typo"));
+        assert_eq!(message.unwrap(), "feat: typo\n\nRefs: #42");
+    }
+
+    #[test]
+    fn skips_the_footer_when_the_ticket_is_already_mentioned_or_unknown() {
+        let options = typed(CommitType::Fix, None);
+        let message = build_message("ABC-123 login", &options, on("ABC-123-login"));
+        assert_eq!(message.unwrap(), "fix: ABC-123 login");
+
+        let message = build_message("login", &options, || None);
+        assert_eq!(message.unwrap(), "fix: login");
+    }
+
+    #[test]
+    fn conventional_remotes_accept_an_existing_prefix_and_refuse_the_rest() {
+        let options = MessageOptions {
+            conventional: true,
+            ..Default::default()
+        };
+        let message = build_message("fix(auth)!: drop v1 tokens", &options, on("7_tokens"));
+        assert_eq!(message.unwrap(), "fix(auth)!: drop v1 tokens\n\nRefs: #7");
+
+        assert!(build_message("drop v1 tokens", &options, on("main")).is_err());
+    }
+
+    #[test]
+    fn recognizes_conventional_messages() {
+        assert!(is_conventional("feat: add sso"));
+        assert!(is_conventional("fix(api/v2): handle 404"));
+        assert!(is_conventional("refactor!: rename config keys"));
+        assert!(!is_conventional("feature: add sso"));
+        assert!(!is_conventional("feat:add sso"));
+        assert!(!is_conventional("feat(): add sso"));
+        assert!(!is_conventional("Feat: add sso"));
+    }
+
+    #[test]
+    fn escapes_branches_in_gitlab_merge_request_pages() {
+        let web_url = "https://gitlab.example.com/grp/sub/app";
+        let url = new_merge_request_url(web_url, HostKind::GitLab, "feat/a+b#1", "main");
+        assert_eq!(
+            url.unwrap(),
+            "https://gitlab.example.com/grp/sub/app/-/merge_requests/new\
+             ?merge_request%5Bsource_branch%5D=feat%2Fa%2Bb%231\
+             &merge_request%5Btarget_branch%5D=main"
+        );
+    }
+
+    #[test]
+    fn escapes_branches_in_github_compare_pages() {
+        let web_url = "https://github.com/grp/app/";
+        let url = new_merge_request_url(web_url, HostKind::GitHub, "feat/50% off#2", "main");
+        assert_eq!(
+            url.unwrap(),
+            "https://github.com/grp/app/compare/main...feat/50%25%20off%232?expand=1"
+        );
+    }
+
+    #[test]
+    fn finds_tickets_in_branch_names() {
+        assert_eq!(
+            ticket_from_branch("feature/ABC-123-login").as_deref(),
+            Some("ABC-123")
+        );
+        assert_eq!(ticket_from_branch("PROJ2-7").as_deref(), Some("PROJ2-7"));
+        assert_eq!(ticket_from_branch("42-fix-typo").as_deref(), Some("#42"));
+        assert_eq!(
+            ticket_from_branch("bugfix/17_crash").as_deref(),
+            Some("#17")
+        );
+        assert_eq!(ticket_from_branch("release-2024").as_deref(), None);
+        assert_eq!(ticket_from_branch("main").as_deref(), None);
+        assert_eq!(ticket_from_branch("abc-123-lowercase").as_deref(), None);
+    }
+}
@@ -1,34 +1,96 @@
-use crate::git::{GitRepo, RemoteSettings};
+use crate::git::{
+    CloneProtocol, CloneSource, GitRepo, HostKind, ProjectMetadata, RemoteProject, RemoteSettings,
+    TrunkHint, DEFAULT_LAYOUT,
+};
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
+use regex::{Captures, Regex};
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::collections::{BTreeMap, HashMap};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::{env, fs};
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 struct InventoryToml {
-    // groups -> slug -> [project_name]
+    // groups -> slug -> [project]
     #[serde(default)]
     groups: InventoryGroups,
 }
 
+/// One project of an inventory file, with whatever the last sync learned about it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "InventoryEntry")]
+pub struct InventoryProject {
+    pub name: ProjectName,
+    #[serde(flatten)]
+    pub metadata: ProjectMetadata,
+}
+
+/// Inventories written before metadata was recorded list bare project names.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum InventoryEntry {
+    Name(ProjectName),
+    Project {
+        name: ProjectName,
+        #[serde(flatten)]
+        metadata: ProjectMetadata,
+    },
+}
+
+impl From<InventoryEntry> for InventoryProject {
+    fn from(entry: InventoryEntry) -> Self {
+        match entry {
+            InventoryEntry::Name(name) => InventoryProject {
+                name,
+                metadata: ProjectMetadata::default(),
+            },
+            InventoryEntry::Project { name, metadata } => InventoryProject { name, metadata },
+        }
+    }
+}
+
+/// Things gits writes itself, kept apart from the hand-edited config.toml.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StateToml {
+    #[serde(default)]
+    remotes: BTreeMap<Host, RemoteState>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RemoteState {
+    // RFC3339, same as `last_pull` in config.toml
+    last_pull: Option<String>,
+    /// "slug/repo" paths added from the `list` picker, on top of the configured `watch_projects`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    watch_projects: Vec<String>,
+}
+
 type Slug = String;
 type ProjectName = String;
 type Host = String;
-type InventoryGroups = BTreeMap<Slug, Vec<ProjectName>>;
+type InventoryGroups = BTreeMap<Slug, Vec<InventoryProject>>;
 pub trait GitsConfig {
     // fn host(&self) -> String;
     fn build() -> Self;
 
     fn get_inventory(&self) -> anyhow::Result<HashMap<Host, InventoryGroups>>;
-    fn get_repos(&self) -> Vec<GitRepo>;
+    fn get_repos(&self) -> Result<Vec<GitRepo>>;
+    fn get_projects(&self) -> Result<Vec<RemoteProject>>;
+    fn get_host_repos(&self, host: &str) -> Result<Vec<GitRepo>>;
+    fn get_host_projects(&self, host: &str) -> Result<Vec<RemoteProject>>;
+    fn resolve_project(&self, origin: Option<&GitRepo>, clone: &Path) -> Option<RemoteProject>;
+    fn find_repo(&self, name: &str) -> Result<Option<GitRepo>>;
+    fn repo_path(&self, repo: &GitRepo) -> PathBuf;
     fn get_private_token(&self, host: Host) -> String;
-    fn get_last_sync(&self, host: Host) -> DateTime<Utc>;
-    fn get_api_url(&self, host: Host) -> String;
+    fn get_last_pull(&self, host: &str) -> Result<DateTime<Utc>>;
+    fn set_last_pull(&self, host: &str, last_pull: DateTime<Utc>) -> Result<()>;
+    fn add_watched(&self, git_repos: &[GitRepo]) -> Result<()>;
 
-    fn add_to_inventory(&self, git_repo: &GitRepo) -> Result<()>;
+    fn upsert_inventory(&self, host: &str, projects: &[RemoteProject]) -> Result<()>;
+    fn remove_from_inventory(&self, host: &str, git_repos: &[GitRepo]) -> Result<()>;
+    fn rename_inventory(&self, from: &str, to: &str) -> Result<bool>;
     fn get_remotes_config(&self) -> anyhow::Result<RemotesConfig>;
 }
 pub struct RealGitsConfig {}
@@ -38,6 +100,214 @@ pub struct RemotesConfig {
     pub remotes: HashMap<Host, RemoteSettings>,
 }
 
+impl RemotesConfig {
+    /// Where the clone of `repo` lives. Hosts without a remote section
+    /// fall back to `~/{host}/{slug}/{repo}`.
+    pub fn repo_path(&self, repo: &GitRepo) -> PathBuf {
+        match self.remotes.get(&repo.host) {
+            Some(settings) => settings.repo_path(repo),
+            None => layout_path(DEFAULT_LAYOUT, &home_dir().join(&repo.host), repo),
+        }
+    }
+
+    /// How to clone `repo`. Hosts without a remote section are cloned over ssh.
+    pub fn clone_source(&self, repo: &GitRepo, metadata: &ProjectMetadata) -> CloneSource {
+        match self.remotes.get(&repo.host) {
+            Some(settings) => settings.clone_source(repo, metadata),
+            None => CloneSource {
+                url: metadata
+                    .ssh_url
+                    .clone()
+                    .unwrap_or_else(|| default_ssh_url(&repo.host, repo)),
+                credential_helper: None,
+            },
+        }
+    }
+
+    /// The host urls of `repo` are pieced together from when sync recorded none:
+    /// its remote's web host, or `repo.host` itself for hosts without a remote section.
+    pub fn web_host<'a>(&'a self, repo: &'a GitRepo) -> &'a str {
+        self.remotes
+            .get(&repo.host)
+            .and_then(RemoteSettings::web_host)
+            .unwrap_or(&repo.host)
+    }
+
+    /// The web page of `repo`, as sync recorded it or pieced together from host and path.
+    pub fn web_url(&self, repo: &GitRepo, metadata: &ProjectMetadata) -> String {
+        metadata.web_url.clone().unwrap_or_else(|| {
+            format!(
+                "https://{}/{}/{}",
+                self.web_host(repo),
+                repo.slug,
+                repo.repo_name
+            )
+        })
+    }
+
+    /// The configured and recorded trunk of `repo`.
+    pub fn trunk_hint(&self, repo: &GitRepo, metadata: &ProjectMetadata) -> TrunkHint {
+        let configured = self.remotes.get(&repo.host).and_then(|settings| {
+            settings
+                .trunks
+                .get(&format!("{}/{}", repo.slug, repo.repo_name))
+                .or(settings.trunk.as_ref())
+                .cloned()
+        });
+        TrunkHint {
+            configured,
+            recorded: metadata.default_branch.clone(),
+        }
+    }
+
+    /// The name in config.toml of the remote `origin` points at: the remote
+    /// named after its host, else the one whose `ssh_host` alias or `api_url`
+    /// host it is (`api.github.com` standing for `github.com`).
+    pub fn remote_name_for(&self, origin: &GitRepo) -> Option<&str> {
+        if let Some((name, _)) = self.remotes.get_key_value(&origin.host) {
+            return Some(name);
+        }
+        let by_ssh_host = self.remotes.iter().find(|(_, settings)| {
+            settings.ssh_host.as_deref().is_some_and(|alias| {
+                alias.rsplit_once('@').map_or(alias, |(_, h)| h) == origin.host
+            })
+        });
+        let by_api_host = || {
+            self.remotes.iter().find(|(_, settings)| {
+                url_host(&settings.api_url).is_some_and(|host| {
+                    host == origin.host || host.strip_prefix("api.") == Some(&origin.host)
+                })
+            })
+        };
+        by_ssh_host
+            .or_else(by_api_host)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Whether `repo` is in its remote's watch set. Repos of unknown hosts are never watched.
+    pub fn is_watched(&self, repo: &GitRepo) -> bool {
+        self.remotes
+            .get(&repo.host)
+            .is_some_and(|settings| settings.watch_set().matches(repo))
+    }
+}
+
+impl RemoteSettings {
+    pub fn repo_path(&self, repo: &GitRepo) -> PathBuf {
+        let root = expand_path(&self.project_directory)
+            .expect("project_directory is checked when config.toml is loaded");
+        layout_path(&self.layout, &root, repo)
+    }
+
+    /// The host of the remote's web pages and clone urls: the one in `api_url`,
+    /// `api.github.com` standing for `github.com`. The remote's name in
+    /// config.toml can be anything, so it is no host to connect to.
+    pub fn web_host(&self) -> Option<&str> {
+        let host = url_host(&self.api_url)?;
+        Some(match self.host_kind {
+            HostKind::GitHub => host.strip_prefix("api.").unwrap_or(host),
+            HostKind::GitLab => host,
+        })
+    }
+
+    /// Where to clone `repo` from over the configured protocol. Urls the API
+    /// reported are preferred over ones pieced together from host and path.
+    pub fn clone_source(&self, repo: &GitRepo, metadata: &ProjectMetadata) -> CloneSource {
+        let host = self.web_host().unwrap_or(&repo.host);
+        let https_url = || {
+            metadata
+                .https_url
+                .clone()
+                .unwrap_or_else(|| format!("https://{}/{}/{}.git", host, repo.slug, repo.repo_name))
+        };
+        match self.clone_protocol {
+            CloneProtocol::Ssh => CloneSource {
+                url: match &self.ssh_host {
+                    Some(alias) => format!("{}:{}/{}.git", alias, repo.slug, repo.repo_name),
+                    None => metadata
+                        .ssh_url
+                        .clone()
+                        .unwrap_or_else(|| default_ssh_url(host, repo)),
+                },
+                credential_helper: None,
+            },
+            CloneProtocol::Https => CloneSource {
+                url: https_url(),
+                credential_helper: None,
+            },
+            CloneProtocol::HttpsToken => {
+                let username = match self.host_kind {
+                    HostKind::GitHub => "x-access-token",
+                    HostKind::GitLab => "oauth2",
+                };
+                // the helper names the variable, so the token itself never lands in .git/config
+                CloneSource {
+                    url: https_url(),
+                    credential_helper: Some(format!(
+                        "!f() {{ echo username={}; echo \"password=${}\"; }}; f",
+                        username, self.token
+                    )),
+                }
+            }
+        }
+    }
+}
+
+/// The host of a `scheme://[user@]host[:port]/...` url.
+fn url_host(url: &str) -> Option<&str> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split('/').next()?;
+    let host = authority.rsplit_once('@').map_or(authority, |(_, h)| h);
+    let host = host.split(':').next()?;
+    (!host.is_empty()).then_some(host)
+}
+
+fn default_ssh_url(host: &str, repo: &GitRepo) -> String {
+    format!("git@{}:{}/{}.git", host, repo.slug, repo.repo_name)
+}
+
+fn layout_path(layout: &str, root: &Path, repo: &GitRepo) -> PathBuf {
+    PathBuf::from(
+        layout
+            .replace("{root}", &root.to_string_lossy())
+            .replace("{host}", &repo.host)
+            .replace("{slug}", &repo.slug)
+            .replace("{repo}", &repo.repo_name),
+    )
+}
+
+/// Expands `~`, `$VAR` and `${VAR}` in a configured path.
+/// Relative paths are taken relative to the home directory, like the old `~/{host}` default.
+/// A variable that is not set is an error rather than an empty string.
+pub fn expand_path(path: &str) -> Result<PathBuf> {
+    let vars = Regex::new(r"\$\{(\w+)\}|\$(\w+)").expect("failed to parse regex");
+    let mut unset = None;
+    let expanded = vars.replace_all(path, |caps: &Captures| {
+        let name = caps.get(1).or_else(|| caps.get(2)).unwrap().as_str();
+        env::var(name).unwrap_or_else(|_| {
+            unset.get_or_insert_with(|| name.to_string());
+            String::new()
+        })
+    });
+    if let Some(name) = unset {
+        anyhow::bail!("{} uses ${}, which is not set", path, name);
+    }
+
+    let expanded = match expanded.strip_prefix('~') {
+        Some(rest) => home_dir().join(rest.trim_start_matches('/')),
+        None => PathBuf::from(expanded.as_ref()),
+    };
+    if expanded.is_relative() {
+        Ok(home_dir().join(expanded))
+    } else {
+        Ok(expanded)
+    }
+}
+
+fn home_dir() -> PathBuf {
+    dirs::home_dir().expect("home dir not found")
+}
+
 impl GitsConfig for RealGitsConfig {
     fn build() -> Self {
         RealGitsConfig {}
@@ -64,9 +334,30 @@ impl GitsConfig for RealGitsConfig {
         let raw = fs::read_to_string(&config_path)
             .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", config_path.display(), e))?;
 
-        let config: RemotesConfig = toml::from_str(&raw)
+        let mut config: RemotesConfig = toml::from_str(&raw)
             .map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", config_path.display(), e))?;
 
+        for (host, settings) in &config.remotes {
+            let paths = [
+                Some(&settings.project_directory),
+                settings.archive_directory.as_ref(),
+            ];
+            for path in paths.into_iter().flatten() {
+                expand_path(path)
+                    .with_context(|| format!("remote {} in {}", host, config_path.display()))?;
+            }
+        }
+
+        for (host, state) in read_state_or_default().remotes {
+            if let Some(settings) = config.remotes.get_mut(&host) {
+                for project in state.watch_projects {
+                    if !settings.watch_projects.contains(&project) {
+                        settings.watch_projects.push(project);
+                    }
+                }
+            }
+        }
+
         Ok(config)
     }
 
@@ -76,14 +367,14 @@ impl GitsConfig for RealGitsConfig {
         remotes_config
             .remotes
             .keys()
-            .into_iter()
             .map(|host| {
                 let inventory_path = dirs::home_dir()
                     .unwrap()
                     .join(format!(".config/gits/{}.toml", host));
 
+                // a remote that was never synced has nothing inventoried yet
                 if !inventory_path.exists() {
-                    anyhow::bail!("No inventory file found at {}.", inventory_path.display());
+                    return Ok((host.clone(), InventoryGroups::default()));
                 }
 
                 let raw = fs::read_to_string(&inventory_path).map_err(|e| {
@@ -100,22 +391,119 @@ impl GitsConfig for RealGitsConfig {
             })
             .collect()
     }
-    fn get_repos(&self) -> Vec<GitRepo> {
-        let mut repos = Vec::new();
+    fn get_repos(&self) -> Result<Vec<GitRepo>> {
+        Ok(self
+            .get_projects()?
+            .into_iter()
+            .map(|project| project.repo)
+            .collect())
+    }
 
-        for (host, groups) in self.get_inventory().unwrap() {
-            for (slug, projects) in groups {
-                for project in projects {
-                    repos.push(GitRepo {
-                        host: host.clone(),
-                        slug: slug.clone(),
-                        repo_name: project,
+    /// Every inventoried repo with what the last sync recorded about it.
+    fn get_projects(&self) -> Result<Vec<RemoteProject>> {
+        let mut projects = Vec::new();
+
+        for (host, groups) in self.get_inventory()? {
+            for (slug, entries) in groups {
+                for entry in entries {
+                    projects.push(RemoteProject {
+                        repo: GitRepo {
+                            host: host.clone(),
+                            slug: slug.clone(),
+                            repo_name: entry.name,
+                        },
+                        metadata: entry.metadata,
                     });
                 }
             }
         }
 
-        repos
+        Ok(projects)
+    }
+
+    /// The inventory of one host; empty before its first sync.
+    fn get_host_repos(&self, host: &str) -> Result<Vec<GitRepo>> {
+        Ok(self
+            .get_host_projects(host)?
+            .into_iter()
+            .map(|project| project.repo)
+            .collect())
+    }
+
+    /// The inventory of one host with what the last sync recorded; empty before its first sync.
+    fn get_host_projects(&self, host: &str) -> Result<Vec<RemoteProject>> {
+        let path = inventory_path_for(host);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let raw = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let inventory: InventoryToml =
+            toml::from_str(&raw).with_context(|| format!("Failed to parse {}", path.display()))?;
+
+        Ok(inventory
+            .groups
+            .into_iter()
+            .flat_map(|(slug, projects)| {
+                projects.into_iter().map(move |project| RemoteProject {
+                    repo: GitRepo {
+                        host: host.to_string(),
+                        slug: slug.clone(),
+                        repo_name: project.name,
+                    },
+                    metadata: project.metadata,
+                })
+            })
+            .collect())
+    }
+
+    /// The project a clone belongs to, with `host` set to the name of its remote
+    /// in config.toml rather than the host in its origin url. The remote is found
+    /// from `origin`, or else by looking for an inventoried project cloned at `clone`.
+    /// Projects that are not inventoried come back without metadata.
+    fn resolve_project(&self, origin: Option<&GitRepo>, clone: &Path) -> Option<RemoteProject> {
+        let remotes = self.get_remotes_config().ok()?;
+        let named = origin.and_then(|origin| Some((origin, remotes.remote_name_for(origin)?)));
+        if let Some((origin, name)) = named {
+            let repo = GitRepo {
+                host: name.to_string(),
+                ..origin.clone()
+            };
+            let metadata = self
+                .get_host_projects(name)
+                .unwrap_or_default()
+                .into_iter()
+                .find(|project| project.repo == repo)
+                .map(|project| project.metadata)
+                .unwrap_or_default();
+            return Some(RemoteProject { repo, metadata });
+        }
+
+        // e.g. an origin on a local mirror, or a host no remote mentions
+        let clone = fs::canonicalize(clone).ok()?;
+        remotes
+            .remotes
+            .keys()
+            .flat_map(|name| self.get_host_projects(name).unwrap_or_default())
+            .find(|project| {
+                fs::canonicalize(remotes.repo_path(&project.repo)).ok() == Some(clone.clone())
+            })
+    }
+
+    /// Looks up an inventoried repo by "slug/repo" or "host/slug/repo".
+    fn find_repo(&self, name: &str) -> Result<Option<GitRepo>> {
+        let name = name.trim_end_matches('/');
+        Ok(self.get_repos()?.into_iter().find(|repo| {
+            let path = format!("{}/{}", repo.slug, repo.repo_name);
+            name == path || name == format!("{}/{}", repo.host, path)
+        }))
+    }
+
+    fn repo_path(&self, repo: &GitRepo) -> PathBuf {
+        match self.get_remotes_config() {
+            Ok(remotes) => remotes.repo_path(repo),
+            Err(_) => layout_path(DEFAULT_LAYOUT, &home_dir().join(&repo.host), repo),
+        }
     }
 
     fn get_private_token(&self, host: Host) -> String {
@@ -131,73 +519,351 @@ impl GitsConfig for RealGitsConfig {
         env::var(token_env_location).unwrap_or_default()
     }
 
-    fn get_last_sync(&self, host: Host) -> DateTime<Utc> {
-        self.get_remotes_config()
-            .unwrap()
+    /// Merges a whole sync result for `host` with a single rewrite of its inventory.
+    fn upsert_inventory(&self, host: &str, projects: &[RemoteProject]) -> Result<()> {
+        update_inventory(host, |inv| {
+            for project in projects {
+                upsert_project(inv, &project.repo, Some(&project.metadata));
+            }
+            // written even when the sync found nothing, so the remote has an inventory afterwards
+            true
+        })
+    }
+
+    fn remove_from_inventory(&self, host: &str, git_repos: &[GitRepo]) -> Result<()> {
+        update_inventory(host, |inv| {
+            for git_repo in git_repos {
+                if let Some(projects) = inv.groups.get_mut(&git_repo.slug) {
+                    projects.retain(|project| project.name != git_repo.repo_name);
+                }
+            }
+            inv.groups.retain(|_, projects| !projects.is_empty());
+            !git_repos.is_empty()
+        })
+    }
+
+    /// Moves the inventory of `from` to `to`, unless `to` already has one.
+    /// Returns whether anything moved.
+    fn rename_inventory(&self, from: &str, to: &str) -> Result<bool> {
+        let (from_path, to_path) = (inventory_path_for(from), inventory_path_for(to));
+        if from == to || !from_path.exists() || to_path.exists() {
+            return Ok(false);
+        }
+        fs::rename(&from_path, &to_path)
+            .with_context(|| format!("moving {} -> {}", from_path.display(), to_path.display()))?;
+        Ok(true)
+    }
+
+    /// The sync watermark for `host`: what the last successful sync recorded
+    /// in the state file, or `last_pull` from config.toml before the first one.
+    fn get_last_pull(&self, host: &str) -> Result<DateTime<Utc>> {
+        let recorded = read_state_or_default()
             .remotes
-            .get(&host)
-            .expect("it to work")
-            .last_pull
+            .remove(host)
+            .and_then(|state| state.last_pull);
+        let last_pull = match recorded {
+            Some(last_pull) => last_pull,
+            None => self
+                .get_remotes_config()?
+                .remotes
+                .get(host)
+                .with_context(|| format!("no remote named {} in config.toml", host))?
+                .last_pull
+                .clone(),
+        };
+        last_pull
             .parse::<DateTime<Utc>>()
-            .expect("failed to parse datetime")
+            .with_context(|| format!("failed to parse last_pull {:?} for {}", last_pull, host))
     }
-    fn get_api_url(&self, host: Host) -> String {
-        self.get_remotes_config()
-            .unwrap()
-            .remotes
-            .get(&host)
-            .expect("it to work")
-            .api_url
-            .clone()
+
+    fn set_last_pull(&self, host: &str, last_pull: DateTime<Utc>) -> Result<()> {
+        update_state(|state| {
+            state.remotes.entry(host.to_string()).or_default().last_pull =
+                Some(last_pull.to_rfc3339());
+        })
     }
 
-    fn add_to_inventory(&self, git_repo: &GitRepo) -> Result<()> {
-        let path = inventory_path_for(&git_repo.host);
+    /// Adds repos to their remotes' watch sets. They are kept in the state file
+    /// so the hand-edited config.toml is never rewritten.
+    fn add_watched(&self, git_repos: &[GitRepo]) -> Result<()> {
+        update_state(|state| {
+            for git_repo in git_repos {
+                let watched = &mut state
+                    .remotes
+                    .entry(git_repo.host.clone())
+                    .or_default()
+                    .watch_projects;
+                let project = format!("{}/{}", git_repo.slug, git_repo.repo_name);
+                if !watched.contains(&project) {
+                    watched.push(project);
+                }
+            }
+        })
+    }
+}
 
-        // Ensure directory exists
-        if let Some(dir) = path.parent() {
-            fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+fn upsert_project(inv: &mut InventoryToml, git_repo: &GitRepo, metadata: Option<&ProjectMetadata>) {
+    let entry = inv.groups.entry(git_repo.slug.clone()).or_default();
+    match entry
+        .iter_mut()
+        .find(|project| project.name == git_repo.repo_name)
+    {
+        Some(project) => {
+            if let Some(metadata) = metadata {
+                project.metadata = metadata.clone();
+            }
         }
-        // Read existing or start fresh
-        let raw = fs::read_to_string(&path).unwrap_or_else(|_| "[groups]\n".to_owned());
-        let mut inv: InventoryToml =
-            toml::from_str(&raw).unwrap_or_else(|_| InventoryToml::default());
-
-        // Upsert the project under the slug
-        let entry = inv.groups.entry(git_repo.slug.clone()).or_default();
+        None => {
+            entry.push(InventoryProject {
+                name: git_repo.repo_name.clone(),
+                metadata: metadata.cloned().unwrap_or_default(),
+            });
+            // Keep a stable on-disk order
+            entry.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+    }
+}
 
-        // Use a set to dedupe, then re-sort for stable on-disk order
-        let mut set: BTreeSet<String> = entry.drain(..).collect();
-        set.insert(git_repo.repo_name.clone());
-        *entry = set.into_iter().collect(); // sorted
+/// One read-modify-write of a host's inventory. `change` returns whether it
+/// changed anything worth writing. An exclusive lock on a `.lock` file next to
+/// the inventory is held throughout, so concurrent runs take turns instead of
+/// overwriting each other's changes.
+fn update_inventory(host: &str, change: impl FnOnce(&mut InventoryToml) -> bool) -> Result<()> {
+    let path = inventory_path_for(host);
+    let _lock = lock_beside(&path)?;
 
-        // Serialize deterministically
-        let serialized = toml::to_string_pretty(&inv).context("serializing inventory")?;
+    let mut inv: InventoryToml = if path.exists() {
+        let raw = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&raw).with_context(|| format!("Failed to parse {}", path.display()))?
+    } else {
+        InventoryToml::default()
+    };
+    if !change(&mut inv) {
+        return Ok(());
+    }
 
-        // Atomic write
-        let mut tmp = path.clone();
-        tmp.set_extension("toml.tmp");
-        {
-            let mut f = fs::OpenOptions::new()
-                .create(true)
-                .write(true)
-                .truncate(true)
-                .open(&tmp)
-                .with_context(|| format!("opening temp file {}", tmp.display()))?;
-            f.write_all(serialized.as_bytes())
-                .context("writing temp file")?;
-            f.flush().ok();
-        }
-        fs::rename(&tmp, &path)
-            .with_context(|| format!("renaming {} -> {}", tmp.display(), path.display()))?;
+    // Serialize deterministically
+    let serialized = toml::to_string_pretty(&inv).context("serializing inventory")?;
+    write_atomically(&path, &serialized)
+    // the lock is released when `_lock` is dropped
+}
 
-        Ok(())
+/// Creates the directory of `path` and takes an exclusive lock on a `.lock`
+/// file next to it, held until the returned file is dropped.
+fn lock_beside(path: &Path) -> Result<fs::File> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
     }
+    let lock_path = path.with_extension("toml.lock");
+    let lock = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("opening {}", lock_path.display()))?;
+    lock.lock()
+        .with_context(|| format!("locking {}", lock_path.display()))?;
+    Ok(lock)
 }
 
 fn inventory_path_for(host: &str) -> PathBuf {
-    dirs::home_dir()
-        .expect("home dir not found")
-        .join(".config/gits")
-        .join(format!("{host}.toml"))
+    home_dir().join(".config/gits").join(format!("{host}.toml"))
+}
+
+fn state_path() -> PathBuf {
+    home_dir().join(".config/gits/state.toml")
+}
+
+fn read_state() -> Result<StateToml> {
+    let path = state_path();
+    if !path.exists() {
+        return Ok(StateToml::default());
+    }
+    let raw =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    toml::from_str(&raw).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// The state for reading only: a state file that cannot be read or parsed
+/// is reported and ignored, so it never stops commands that merely consult it.
+fn read_state_or_default() -> StateToml {
+    read_state().unwrap_or_else(|e| {
+        eprintln!("warning: ignoring the state file: {:#}", e);
+        StateToml::default()
+    })
+}
+
+/// One locked read-modify-write of the state file, like `update_inventory`.
+/// A state file that does not parse is left alone rather than overwritten.
+fn update_state(change: impl FnOnce(&mut StateToml)) -> Result<()> {
+    let path = state_path();
+    let _lock = lock_beside(&path)?;
+    let mut state = read_state()?;
+    change(&mut state);
+
+    let serialized = toml::to_string_pretty(&state).context("serializing state")?;
+    write_atomically(&path, &serialized)
+    // the lock is released when `_lock` is dropped
+}
+
+/// Writes to a temp file next to `path` and renames it into place,
+/// so readers never see a half-written file.
+fn write_atomically(path: &Path, contents: &str) -> Result<()> {
+    let mut tmp = path.to_path_buf();
+    tmp.set_extension("toml.tmp");
+    {
+        let mut f = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&tmp)
+            .with_context(|| format!("opening temp file {}", tmp.display()))?;
+        f.write_all(contents.as_bytes())
+            .context("writing temp file")?;
+        f.flush().ok();
+    }
+    fs::rename(&tmp, path)
+        .with_context(|| format!("renaming {} -> {}", tmp.display(), path.display()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn remotes() -> RemotesConfig {
+        toml::from_str(
+            r#"
+            [remotes.mock-gitlab]
+            token = "GITLAB_TOKEN"
+            api_url = "https://gitlab.example.com/api/v4"
+            host_kind = "gitlab"
+            project_directory = "~/work"
+            watch_groups = []
+            watch_projects = []
+            last_pull = "2025-01-01T00:00:00Z"
+            trunk = "develop"
+            trunks = { "platform/legacy" = "master" }
+
+            [remotes.aliased]
+            token = "OTHER_TOKEN"
+            api_url = "https://git.internal:8443/api/v4"
+            host_kind = "gitlab"
+            project_directory = "~/internal"
+            watch_groups = []
+            watch_projects = []
+            last_pull = "2025-01-01T00:00:00Z"
+            ssh_host = "git@work-gitlab"
+
+            [remotes.work-github]
+            token = "GITHUB_TOKEN"
+            api_url = "https://api.github.com"
+            host_kind = "github"
+            project_directory = "~/github"
+            watch_groups = []
+            watch_projects = []
+            last_pull = "2025-01-01T00:00:00Z"
+
+            [remotes."codeberg.org"]
+            token = "CODEBERG_TOKEN"
+            api_url = "https://codeberg.org/api/v1"
+            host_kind = "github"
+            project_directory = "~/codeberg"
+            watch_groups = []
+            watch_projects = []
+            last_pull = "2025-01-01T00:00:00Z"
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn origin(host: &str, slug: &str, repo_name: &str) -> GitRepo {
+        GitRepo {
+            host: host.to_string(),
+            slug: slug.to_string(),
+            repo_name: repo_name.to_string(),
+        }
+    }
+
+    #[test]
+    fn finds_a_remote_named_after_the_origin_host() {
+        let remotes = remotes();
+        let repo = origin("codeberg.org", "me", "dotfiles");
+        assert_eq!(remotes.remote_name_for(&repo), Some("codeberg.org"));
+    }
+
+    #[test]
+    fn finds_a_remote_with_a_non_url_name_by_its_api_host() {
+        let remotes = remotes();
+        let repo = origin("gitlab.example.com", "platform", "api");
+        assert_eq!(remotes.remote_name_for(&repo), Some("mock-gitlab"));
+
+        let repo = origin("github.com", "octo", "hello");
+        assert_eq!(remotes.remote_name_for(&repo), Some("work-github"));
+    }
+
+    #[test]
+    fn finds_a_remote_by_its_ssh_host_alias() {
+        let remotes = remotes();
+        let repo = origin("work-gitlab", "team", "tool");
+        assert_eq!(remotes.remote_name_for(&repo), Some("aliased"));
+    }
+
+    #[test]
+    fn finds_no_remote_for_an_unknown_host() {
+        let remotes = remotes();
+        assert_eq!(
+            remotes.remote_name_for(&origin("gitlab.com", "a", "b")),
+            None
+        );
+    }
+
+    #[test]
+    fn trunk_overrides_apply_under_a_non_url_remote_name() {
+        let remotes = remotes();
+        let metadata = ProjectMetadata {
+            default_branch: Some("main".to_string()),
+            ..Default::default()
+        };
+        let name = remotes
+            .remote_name_for(&origin("gitlab.example.com", "platform", "legacy"))
+            .unwrap();
+
+        let legacy = origin(name, "platform", "legacy");
+        let hint = remotes.trunk_hint(&legacy, &metadata);
+        assert_eq!(hint.configured.as_deref(), Some("master"));
+        assert_eq!(hint.recorded.as_deref(), Some("main"));
+
+        let api = origin(name, "platform", "api");
+        let hint = remotes.trunk_hint(&api, &metadata);
+        assert_eq!(hint.configured.as_deref(), Some("develop"));
+    }
+
+    #[test]
+    fn pieces_urls_together_from_the_api_host_not_the_remote_name() {
+        let remotes = remotes();
+        let metadata = ProjectMetadata::default();
+
+        let repo = origin("work-github", "octo", "hello");
+        assert_eq!(
+            remotes.clone_source(&repo, &metadata).url,
+            "git@github.com:octo/hello.git"
+        );
+        assert_eq!(
+            remotes.web_url(&repo, &metadata),
+            "https://github.com/octo/hello"
+        );
+
+        let repo = origin("mock-gitlab", "platform", "api");
+        assert_eq!(
+            remotes.clone_source(&repo, &metadata).url,
+            "git@gitlab.example.com:platform/api.git"
+        );
+        assert_eq!(
+            remotes.web_url(&repo, &metadata),
+            "https://gitlab.example.com/platform/api"
+        );
+    }
 }
@@ -0,0 +1,142 @@
+use crate::git::{AheadBehind, BranchStatus, GitRepo, WorktreeStatus};
+use chrono::{DateTime, Utc};
+use std::fmt;
+
+/// One line of `status --all`.
+#[derive(Debug)]
+pub struct DashboardRow {
+    pub name: String,
+    pub status: Result<(BranchStatus, WorktreeStatus), String>,
+}
+
+impl DashboardRow {
+    pub fn new(repo: &GitRepo, status: Result<(BranchStatus, WorktreeStatus), String>) -> Self {
+        Self {
+            name: format!("{} {}/{}", repo.host, repo.slug, repo.repo_name),
+            status,
+        }
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        match &self.status {
+            Ok((_, worktree)) => !worktree.is_clean(),
+            Err(_) => false,
+        }
+    }
+
+    pub fn is_behind(&self) -> bool {
+        match &self.status {
+            Ok((branch, _)) => branch.is_behind(),
+            Err(_) => false,
+        }
+    }
+
+    fn columns(&self, now: DateTime<Utc>) -> [String; 5] {
+        match &self.status {
+            Ok((branch, worktree)) => [
+                branch.branch.clone(),
+                branch
+                    .upstream
+                    .map(format_counts)
+                    .unwrap_or_else(|| "no upstream".to_string()),
+                match (&branch.trunk, branch.trunk_ahead_behind) {
+                    (Some(trunk), Some(counts)) => format!("{} {}", trunk, format_counts(counts)),
+                    (Some(trunk), None) => format!("{} ?", trunk),
+                    (None, _) => "no trunk".to_string(),
+                },
+                if worktree.is_clean() {
+                    String::new()
+                } else {
+                    worktree.describe()
+                },
+                branch
+                    .last_commit
+                    .map(|at| format_age(now - at))
+                    .unwrap_or_default(),
+            ],
+            Err(error) => [
+                "error".to_string(),
+                error.lines().next().unwrap_or_default().to_string(),
+                String::new(),
+                String::new(),
+                String::new(),
+            ],
+        }
+    }
+}
+
+pub fn format_counts(counts: AheadBehind) -> String {
+    if counts.ahead == 0 && counts.behind == 0 {
+        "=".to_string()
+    } else {
+        format!("+{} -{}", counts.ahead, counts.behind)
+    }
+}
+
+fn format_age(age: chrono::TimeDelta) -> String {
+    if age.num_days() >= 365 {
+        format!("{}y ago", age.num_days() / 365)
+    } else if age.num_days() > 0 {
+        format!("{}d ago", age.num_days())
+    } else if age.num_hours() > 0 {
+        format!("{}h ago", age.num_hours())
+    } else {
+        format!("{}m ago", age.num_minutes().max(0))
+    }
+}
+
+/// Compact one-line-per-repo view of a whole workspace.
+#[derive(Debug, Default)]
+pub struct Dashboard {
+    rows: Vec<DashboardRow>,
+}
+
+impl Dashboard {
+    pub fn new(rows: Vec<DashboardRow>) -> Self {
+        Self { rows }
+    }
+}
+
+impl fmt::Display for Dashboard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.rows.is_empty() {
+            return writeln!(f, "no cloned repos to show");
+        }
+        let now = Utc::now();
+        let header = [
+            "REPO",
+            "BRANCH",
+            "UPSTREAM",
+            "TRUNK",
+            "DIRTY",
+            "LAST COMMIT",
+        ];
+        let lines = self
+            .rows
+            .iter()
+            .map(|row| {
+                let [branch, upstream, trunk, dirty, age] = row.columns(now);
+                [row.name.clone(), branch, upstream, trunk, dirty, age]
+            })
+            .collect::<Vec<_>>();
+
+        let mut widths = header.map(str::len);
+        for line in &lines {
+            for (width, cell) in widths.iter_mut().zip(line) {
+                *width = (*width).max(cell.len());
+            }
+        }
+
+        let header = header.map(String::from);
+        for line in std::iter::once(&header).chain(&lines) {
+            let row = line
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{:width$}", cell))
+                .collect::<Vec<_>>()
+                .join("  ");
+            writeln!(f, "{}", row.trim_end())?;
+        }
+        Ok(())
+    }
+}
@@ -1,64 +1,278 @@
-use std::collections::HashMap;
-use crate::git::GitRepo;
+use crate::git::{GitRepo, ProjectMetadata, RemoteProject};
+use crate::merge_request::MergeRequest;
+use anyhow::{anyhow, bail, Context};
 use chrono::{DateTime, Utc};
 use regex::Regex;
-use reqwest::Client;
-use reqwest::header::{HeaderMap, ACCEPT, AUTHORIZATION};
+use reqwest::header::{HeaderMap, ACCEPT, AUTHORIZATION, LINK};
+use reqwest::{Client, StatusCode};
 use serde::Deserialize;
+use serde_json::json;
 
 #[derive(Deserialize, Debug)]
 pub struct GitHubResponse {
     full_name: String,
     created_at: String,
+    #[serde(default)]
+    archived: bool,
+    description: Option<String>,
+    default_branch: Option<String>,
+    visibility: Option<String>,
+    #[serde(default)]
+    private: bool,
+    pushed_at: Option<String>,
+    html_url: Option<String>,
+    ssh_url: Option<String>,
+    clone_url: Option<String>,
+    stargazers_count: Option<u64>,
 }
-fn api_url_to_host(url: &str) -> String {
+
+impl GitHubResponse {
+    fn metadata(&self) -> ProjectMetadata {
+        ProjectMetadata {
+            description: self.description.clone().filter(|d| !d.is_empty()),
+            default_branch: self.default_branch.clone(),
+            archived: self.archived,
+            // `visibility` is missing from older GitHub Enterprise servers
+            visibility: self
+                .visibility
+                .clone()
+                .or_else(|| Some(if self.private { "private" } else { "public" }.to_string())),
+            last_activity: self.pushed_at.clone(),
+            web_url: self.html_url.clone(),
+            ssh_url: self.ssh_url.clone(),
+            https_url: self.clone_url.clone(),
+            stars: self.stargazers_count,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct CreatedPullRequest {
+    number: u64,
+    html_url: String,
+}
+
+/// The `watch_groups` entry "@me" means the repos owned by whoever the token belongs to.
+pub const AUTHENTICATED_USER: &str = "@me";
+
+fn github_headers(private_token: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+
+    headers.insert(ACCEPT, "application/vnd.github+json".parse().unwrap());
+    headers.insert("X-GitHub-Api-Version", "2022-11-28".parse().unwrap());
+
+    if !private_token.is_empty() {
+        headers.insert(
+            AUTHORIZATION,
+            format!("Bearer {}", private_token).parse().unwrap(),
+        );
+    }
+    headers
+}
+
+/// What GitHub inventories were named before they were named after their remote:
+/// the api url without its scheme, e.g. `api.github.com`.
+pub fn legacy_inventory_host(api_url: &str) -> String {
     let re = Regex::new("https?://(.+)").expect("failed to parse regex");
-    let caps = re.captures(&url).unwrap();
-    let host = caps.get(1).map_or("", |m| m.as_str());
-    String::from(host)
+    re.captures(api_url)
+        .and_then(|caps| caps.get(1))
+        .map_or(api_url, |m| m.as_str())
+        .to_string()
+}
+
+/// Splits a GitHub `full_name` into owner and repo.
+fn split_full_name(full_name: &str) -> anyhow::Result<(&str, &str)> {
+    full_name.split_once('/').ok_or_else(|| {
+        anyhow!(
+            "GitHub returned {:?} as a full_name, not owner/repo",
+            full_name
+        )
+    })
+}
+
+/// The url of the `rel="next"` page in a GitHub `Link` header, if there is one.
+fn next_page(headers: &HeaderMap) -> Option<String> {
+    let link = headers.get(LINK)?.to_str().ok()?;
+    let re = Regex::new(r#"<([^>]+)>;\s*rel="next""#).expect("failed to parse regex");
+    re.captures(link)
+        .and_then(|caps| caps.get(1))
+        .map(|m| m.as_str().to_string())
 }
 
+/// Lists the repos of a user, an organization, or the authenticated user ("@me"),
+/// newest first, stopping at the first repo created before `last_pull`.
+/// A plain name is tried as an organization first so private org repos are included.
 pub async fn get_watched_github_projects(
     api_url: &str,
     private_token: &str,
     last_pull: &DateTime<Utc>,
     user: String,
     id: String,
-) -> Result<Vec<GitRepo>, reqwest::Error> {
+) -> anyhow::Result<Vec<RemoteProject>> {
     let client = Client::new();
-    let mut repos: Vec<GitRepo> = Vec::new();
-    let mut headers = HeaderMap::new();
+    let mut repos: Vec<RemoteProject> = Vec::new();
+    let headers = github_headers(private_token);
 
-    headers.insert(ACCEPT, "application/vnd.github+json".parse().unwrap());
-    headers.insert("X-GitHub-Api-Version", "2022-11-28".parse().unwrap());
-    
-    if !private_token.is_empty() {
-        headers.insert(AUTHORIZATION, format!("Bearer: {}", private_token).parse().unwrap());
+    let first_pages = if user == AUTHENTICATED_USER {
+        vec![format!(
+            "{}/user/repos?affiliation=owner&sort=created&direction=desc&per_page=100",
+            api_url
+        )]
+    } else {
+        vec![
+            format!(
+                "{}/orgs/{}/repos?sort=created&direction=desc&per_page=100",
+                api_url, user
+            ),
+            format!(
+                "{}/users/{}/repos?sort=created&direction=desc&per_page=100",
+                api_url, user
+            ),
+        ]
+    };
+
+    for first_page in first_pages {
+        let mut next = Some(first_page);
+        let mut page = 1;
+        let mut found = false;
+        while let Some(url) = next.take() {
+            print!("Fetching {} page {}...", user, page);
+            let response = client.get(&url).headers(headers.clone()).send().await?;
+            if page == 1 && response.status() == StatusCode::NOT_FOUND {
+                // not an organization, try the next kind of owner
+                println!("not found");
+                break;
+            }
+            let response = match response.error_for_status() {
+                Ok(response) => response,
+                Err(e) => {
+                    println!("Check vpn connection? {:?}", e);
+                    return Err(e.into());
+                }
+            };
+            found = true;
+            let following = next_page(response.headers());
+            let page_projects: Vec<GitHubResponse> = response.json().await?;
+            let mut reached_last_pull = false;
+            for project in &page_projects {
+                let created_at =
+                    project
+                        .created_at
+                        .parse::<DateTime<Utc>>()
+                        .with_context(|| {
+                            format!(
+                                "parsing created_at {:?} of {}",
+                                project.created_at, project.full_name
+                            )
+                        })?;
+                if created_at < *last_pull {
+                    reached_last_pull = true;
+                    break;
+                }
+                let (owner, repo_name) = split_full_name(&project.full_name)?;
+
+                repos.push(RemoteProject {
+                    repo: GitRepo {
+                        host: id.clone(),
+                        slug: owner.to_string(),
+                        repo_name: repo_name.to_string(),
+                    },
+                    metadata: project.metadata(),
+                })
+            }
+            println!("found {} repos", page_projects.len());
+            if !reached_last_pull {
+                next = following;
+            }
+            page += 1;
+        }
+        if found {
+            break;
+        }
     }
+    Ok(repos)
+}
+
+/// Looks a repo up by "owner/name". GitHub redirects renamed and transferred
+/// repos, so a moved repo comes back under its new name. None when it is gone.
+pub async fn find_github_repo(
+    api_url: &str,
+    private_token: &str,
+    full_name: &str,
+    id: String,
+) -> anyhow::Result<Option<GitRepo>> {
+    let response = Client::new()
+        .get(format!("{}/repos/{}", api_url, full_name))
+        .headers(github_headers(private_token))
+        .send()
+        .await?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let repo: GitHubResponse = response.error_for_status()?.json().await?;
+    let (owner, repo_name) = split_full_name(&repo.full_name)?;
+    Ok(Some(GitRepo {
+        host: id,
+        slug: owner.to_string(),
+        repo_name: repo_name.to_string(),
+    }))
+}
+
+/// Opens a pull request on the repo `full_name` ("owner/name") and returns its web url.
+/// GitHub sets assignees and labels on the pull request's issue, after creating it.
+pub async fn create_github_pull_request(
+    api_url: &str,
+    private_token: &str,
+    full_name: &str,
+    request: &MergeRequest,
+) -> anyhow::Result<String> {
+    let client = Client::new();
+    let headers = github_headers(private_token);
     let response = client
-        .get(format!("{}/users/{}/repos", api_url, user))
-        .headers(headers)
+        .post(format!("{}/repos/{}/pulls", api_url, full_name))
+        .headers(headers.clone())
+        .json(&json!({
+            "title": request.title,
+            "head": request.source_branch,
+            "base": request.target_branch,
+            "body": request.description,
+            "draft": request.draft,
+        }))
         .send()
         .await?;
-    
-    
-        match response.error_for_status() {
-        Ok(response) => {
-            let page_projects: Vec<GitHubResponse> = response.json().await?;
-            page_projects.iter().for_each(|project| {
-                let repo_name = project.full_name.split('/').last().unwrap();
+    let status = response.status();
+    if !status.is_success() {
+        // e.g. 422 when the branch already has an open pull request
+        bail!(
+            "GitHub refused the pull request ({}): {}",
+            status,
+            response.text().await.unwrap_or_default()
+        );
+    }
+    let created: CreatedPullRequest = response.json().await?;
 
-                repos.push(GitRepo {
-                    host: api_url_to_host(api_url),
-                    slug: user.clone(),
-                    repo_name: repo_name.to_string(),
-                })
-            })
+    // the pull request exists now, so failures from here on only cost the extras
+    let issue_url = format!("{}/repos/{}/issues/{}", api_url, full_name, created.number);
+    for (extra, values) in [
+        ("assignees", &request.assignees),
+        ("labels", &request.labels),
+    ] {
+        if values.is_empty() {
+            continue;
         }
-        Err(e) => {
-            println!("Check vpn connection? {:?}", e);
-            return Err(e);
+        let result = client
+            .post(format!("{}/{}", issue_url, extra))
+            .headers(headers.clone())
+            .json(&json!({ extra: values }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(e) = result {
+            eprintln!(
+                "warning: opened {}, but setting its {} failed: {}",
+                created.html_url, extra, e
+            );
         }
     }
-    Ok(repos)
+    Ok(created.html_url)
 }
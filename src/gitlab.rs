@@ -1,6 +1,20 @@
-use crate::git::Project;
+use crate::git::{parse_url, GitRepo, Project};
+use crate::merge_request::MergeRequest;
+use anyhow::{anyhow, bail};
 use chrono::{DateTime, Utc};
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Deserialize, Debug)]
+struct GitLabUser {
+    id: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct CreatedMergeRequest {
+    web_url: String,
+}
 
 pub async fn get_all_gitlab_projects(
     gitlab_api_url: &str,
@@ -55,3 +69,88 @@ pub async fn get_all_gitlab_projects(
 
     Ok(projects)
 }
+
+/// Looks a project up by its full path. GitLab follows the redirect a rename
+/// or transfer leaves behind, so a moved project comes back under its new path.
+/// None when the project is gone.
+pub async fn find_gitlab_project(
+    gitlab_api_url: &str,
+    private_token: &str,
+    path: &str,
+) -> anyhow::Result<Option<GitRepo>> {
+    let response = Client::new()
+        .get(format!(
+            "{}/projects/{}",
+            gitlab_api_url,
+            path.replace('/', "%2F")
+        ))
+        .header("Private-Token", private_token)
+        .send()
+        .await?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let project: Project = response.error_for_status()?.json().await?;
+    Ok(Some(parse_url(&project.ssh_url_to_repo)?))
+}
+
+/// Opens a merge request on the project at `path` and returns its web url.
+/// Assignees are usernames, looked up to the ids GitLab wants.
+pub async fn create_gitlab_merge_request(
+    gitlab_api_url: &str,
+    private_token: &str,
+    path: &str,
+    request: &MergeRequest,
+) -> anyhow::Result<String> {
+    let client = Client::new();
+    let mut assignee_ids = Vec::new();
+    for username in &request.assignees {
+        let users: Vec<GitLabUser> = client
+            .get(format!("{}/users", gitlab_api_url))
+            .header("Private-Token", private_token)
+            .query(&[("username", username)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let user = users
+            .first()
+            .ok_or_else(|| anyhow!("there is no GitLab user {}", username))?;
+        assignee_ids.push(user.id);
+    }
+    let title = if request.draft {
+        format!("Draft: {}", request.title)
+    } else {
+        request.title.clone()
+    };
+
+    let response = client
+        .post(format!(
+            "{}/projects/{}/merge_requests",
+            gitlab_api_url,
+            path.replace('/', "%2F")
+        ))
+        .header("Private-Token", private_token)
+        .json(&json!({
+            "source_branch": request.source_branch,
+            "target_branch": request.target_branch,
+            "title": title,
+            "description": request.description,
+            "assignee_ids": assignee_ids,
+            "labels": request.labels.join(","),
+        }))
+        .send()
+        .await?;
+    let status = response.status();
+    if !status.is_success() {
+        // e.g. 409 when the branch already has an open merge request
+        bail!(
+            "GitLab refused the merge request ({}): {}",
+            status,
+            response.text().await.unwrap_or_default()
+        );
+    }
+    let created: CreatedMergeRequest = response.json().await?;
+    Ok(created.web_url)
+}
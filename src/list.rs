@@ -1,73 +1,424 @@
+use crate::bulk::{self, DEFAULT_JOBS};
+use crate::command_executor::RealCommandExecutor;
 use crate::config::{GitsConfig, RealGitsConfig};
-use crate::git::{parse_url, Git, GitRepo, RealGit};
+use crate::dashboard::format_counts;
+use crate::git::{is_cloned, CloneSource, Git, GitRepo, HostKind, ProjectMetadata, RealGit};
+use crate::report::{Outcome, Report};
 use anyhow::{anyhow, bail, Context, Result};
+use serde::Serialize;
 use skim::options::SkimOptionsBuilder;
 use skim::prelude::*;
 use skim::{Skim, SkimItem, SkimItemReceiver, SkimItemSender, SkimOutput};
 use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::Arc;
 
+/// How many lines of the README the preview shows.
+const README_LINES: usize = 20;
+
+/// One inventory entry in the picker. The preview looks at the local
+/// clone when there is one and shows what sync recorded otherwise.
+struct InventoryItem {
+    repo: GitRepo,
+    metadata: ProjectMetadata,
+    path: PathBuf,
+    source: CloneSource,
+    host_kind: HostKind,
+    /// recorded by sync, else pieced together from the remote's web host
+    web_url: String,
+    watched: bool,
+    /// skim tells ticked items apart by index
+    index: usize,
+}
+
+impl SkimItem for InventoryItem {
+    fn text(&self) -> Cow<'_, str> {
+        Cow::Owned(format!(
+            "{} {}/{}",
+            self.repo.host, self.repo.slug, self.repo.repo_name
+        ))
+    }
+
+    fn get_index(&self) -> usize {
+        self.index
+    }
+
+    fn preview(&self, _context: PreviewContext) -> ItemPreview {
+        let preview = if is_cloned(&self.path) {
+            self.clone_preview()
+        } else {
+            self.metadata_preview()
+        };
+        ItemPreview::Text(preview)
+    }
+}
+
+impl InventoryItem {
+    fn web_url(&self) -> String {
+        self.web_url.clone()
+    }
+
+    fn merge_requests_url(&self) -> String {
+        match self.host_kind {
+            HostKind::GitLab => format!("{}/-/merge_requests", self.web_url()),
+            HostKind::GitHub => format!("{}/pulls", self.web_url()),
+        }
+    }
+
+    fn pipelines_url(&self) -> String {
+        match self.host_kind {
+            HostKind::GitLab => format!("{}/-/pipelines", self.web_url()),
+            HostKind::GitHub => format!("{}/actions", self.web_url()),
+        }
+    }
+
+    fn clone_preview(&self) -> String {
+        // previews only read, so they always run for real, even with --dryrun
+        let git = RealGit::new(&RealCommandExecutor).at(&self.path);
+        let mut preview = format!("cloned at {}\n", self.path.display());
+
+        match git.branch_status() {
+            Ok(status) => {
+                let upstream = status
+                    .upstream
+                    .map(format_counts)
+                    .unwrap_or_else(|| "no upstream".to_string());
+                let _ = writeln!(preview, "branch: {} ({})", status.branch, upstream);
+            }
+            Err(e) => {
+                let _ = writeln!(preview, "branch: {:#}", e);
+            }
+        }
+        match git.worktree_status() {
+            Ok(status) if status.is_clean() => preview.push_str("dirty: no\n"),
+            Ok(status) => {
+                let _ = writeln!(preview, "dirty: {}", status.describe());
+            }
+            Err(e) => {
+                let _ = writeln!(preview, "dirty: {:#}", e);
+            }
+        }
+        match git.last_commit() {
+            Ok(commit) => {
+                let _ = writeln!(preview, "last commit: {}", commit);
+            }
+            Err(e) => {
+                let _ = writeln!(preview, "last commit: {:#}", e);
+            }
+        }
+
+        if let Some((name, head)) = readme_head(&self.path) {
+            let _ = write!(preview, "\n{}:\n{}", name, head);
+        }
+        preview
+    }
+
+    fn metadata_preview(&self) -> String {
+        let metadata = &self.metadata;
+        let mut preview = format!("not cloned (would go to {})\n", self.path.display());
+        if let Some(description) = &metadata.description {
+            let _ = writeln!(preview, "\n{}\n", description);
+        }
+        let fields = [
+            ("default branch", metadata.default_branch.clone()),
+            ("visibility", metadata.visibility.clone()),
+            ("archived", metadata.archived.then(|| "yes".to_string())),
+            ("stars", metadata.stars.map(|stars| stars.to_string())),
+            ("last activity", metadata.last_activity.clone()),
+            ("web", metadata.web_url.clone()),
+            ("ssh", metadata.ssh_url.clone()),
+            ("https", metadata.https_url.clone()),
+        ];
+        let mut known = false;
+        for (label, value) in fields {
+            if let Some(value) = value {
+                let _ = writeln!(preview, "{}: {}", label, value);
+                known = true;
+            }
+        }
+        if !known && metadata.description.is_none() {
+            preview.push_str("no details recorded yet, run `gits sync --full`\n");
+        }
+        preview
+    }
+}
+
+/// The first lines of the clone's README, whatever its case or extension.
+fn readme_head(path: &Path) -> Option<(String, String)> {
+    let readme = fs::read_dir(path)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|file| file.is_file())
+        .find(|file| {
+            file.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.to_lowercase().starts_with("readme"))
+        })?;
+    let contents = fs::read_to_string(&readme).ok()?;
+    let head = contents
+        .lines()
+        .take(README_LINES)
+        .collect::<Vec<_>>()
+        .join("\n");
+    let name = readme.file_name()?.to_string_lossy().to_string();
+    Some((name, head))
+}
+
+/// How many commits the Log action shows.
+const LOG_LINES: usize = 30;
+
+#[derive(Clone, Copy)]
 enum ProjectOptions {
     Remote,
+    Cd,
+    Shell,
+    Editor,
     Clone,
+    Pull,
+    Log,
+    CopyUrl,
+    MergeRequests,
+    Pipelines,
+    Watch,
+    Forget,
 }
 
 impl SkimItem for ProjectOptions {
-    fn text(&self) -> Cow<str> {
+    fn text(&self) -> Cow<'_, str> {
         match self {
             ProjectOptions::Remote => Cow::Borrowed("Remote"),
+            ProjectOptions::Cd => Cow::Borrowed("Cd"),
+            ProjectOptions::Shell => Cow::Borrowed("Shell"),
+            ProjectOptions::Editor => Cow::Borrowed("Editor"),
             ProjectOptions::Clone => Cow::Borrowed("Clone"),
+            ProjectOptions::Pull => Cow::Borrowed("Pull"),
+            ProjectOptions::Log => Cow::Borrowed("Log"),
+            ProjectOptions::CopyUrl => Cow::Borrowed("Clone URL"),
+            ProjectOptions::MergeRequests => Cow::Borrowed("Merge requests"),
+            ProjectOptions::Pipelines => Cow::Borrowed("Pipelines"),
+            ProjectOptions::Watch => Cow::Borrowed("Watch"),
+            ProjectOptions::Forget => Cow::Borrowed("Remove from inventory"),
         }
     }
 }
-fn pick_repo(out: SkimOutput) -> anyhow::Result<GitRepo> {
-    let item = out
-        .selected_items
-        .first()
-        .ok_or_else(|| anyhow::anyhow!("No item selected"))?;
-    let s = item.output();
 
-    let (host, repo_part) = s
-        .split_once(' ')
-        .ok_or_else(|| anyhow::anyhow!("Invalid skim output: {s}"))?;
+impl ProjectOptions {
+    /// The actions that make sense for what was picked: working in a clone
+    /// needs exactly one cloned repo, pulling needs at least one.
+    fn available(items: &[&InventoryItem]) -> Vec<ProjectOptions> {
+        let cloned = items.iter().filter(|item| is_cloned(&item.path)).count();
+        let one_clone = items.len() == 1 && cloned == 1;
+        [
+            (ProjectOptions::Remote, true),
+            (ProjectOptions::Cd, one_clone),
+            (ProjectOptions::Shell, one_clone),
+            (ProjectOptions::Editor, one_clone),
+            (ProjectOptions::Clone, cloned < items.len()),
+            (ProjectOptions::Pull, cloned > 0),
+            (ProjectOptions::Log, one_clone),
+            (ProjectOptions::CopyUrl, true),
+            (ProjectOptions::MergeRequests, true),
+            (ProjectOptions::Pipelines, true),
+            (ProjectOptions::Watch, true),
+            (ProjectOptions::Forget, true),
+        ]
+        .into_iter()
+        .filter_map(|(action, shown)| shown.then_some(action))
+        .collect()
+    }
+}
 
-    let repo = parse_url(&format!("git@{}:{}.git", host, repo_part));
+/// Every ticked entry, or the highlighted one when nothing is ticked.
+fn pick_repos(out: &SkimOutput) -> Vec<&InventoryItem> {
+    out.selected_items
+        .iter()
+        .filter_map(|item| (**item).as_any().downcast_ref::<InventoryItem>())
+        .collect()
+}
+
+/// How `list` prints the inventory when it is not opening the picker.
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum ListFormat {
+    /// "host/slug/repo path", with "(not cloned)" after repos without a clone
+    Plain,
+    /// an array of objects with every recorded field
+    Json,
+    /// tab separated, with a header line
+    Tsv,
+}
 
-    Ok(repo)
+/// Which inventory entries `list` shows, in the picker and in printed output alike.
+#[derive(Debug, Default)]
+pub struct ListFilter {
+    pub host: Option<String>,
+    /// a group and everything below it
+    pub group: Option<String>,
+    pub cloned: Option<bool>,
+    pub watched: bool,
+}
+
+impl ListFilter {
+    fn matches(&self, item: &InventoryItem) -> bool {
+        let group = self.group.as_deref().map(|group| group.trim_matches('/'));
+        self.host
+            .as_ref()
+            .is_none_or(|host| *host == item.repo.host)
+            && group.is_none_or(|group| {
+                item.repo.slug == group || item.repo.slug.starts_with(&format!("{}/", group))
+            })
+            && self
+                .cloned
+                .is_none_or(|cloned| cloned == is_cloned(&item.path))
+            && (!self.watched || item.watched)
+    }
 }
 
-pub fn view_projects(git: &RealGit, config: &RealGitsConfig) {
+/// The inventory of every remote as picker items, in a stable order. Only the
+/// inventories of remotes passing the host filter are read.
+fn inventory_items(config: &RealGitsConfig, filter: &ListFilter) -> Result<Vec<InventoryItem>> {
+    let remotes = config.get_remotes_config()?;
+    let hosts = remotes
+        .remotes
+        .iter()
+        .filter(|(host, _)| filter.host.as_ref().is_none_or(|wanted| wanted == *host))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut items = Vec::new();
+    for (host, settings) in hosts {
+        for project in config.get_host_projects(host)? {
+            let repo = project.repo;
+            let item = InventoryItem {
+                path: remotes.repo_path(&repo),
+                source: remotes.clone_source(&repo, &project.metadata),
+                watched: remotes.is_watched(&repo),
+                web_url: remotes.web_url(&repo, &project.metadata),
+                repo,
+                metadata: project.metadata,
+                host_kind: settings.host_kind,
+                index: items.len(),
+            };
+            if filter.matches(&item) {
+                items.push(item);
+            }
+        }
+    }
+    Ok(items)
+}
+
+#[derive(Serialize)]
+struct ListRow<'a> {
+    host: &'a str,
+    slug: &'a str,
+    name: &'a str,
+    path: String,
+    cloned: bool,
+    watched: bool,
+    #[serde(flatten)]
+    metadata: &'a ProjectMetadata,
+}
+
+/// Prints the filtered inventory for scripts instead of opening the picker.
+pub fn print_projects(
+    config: &RealGitsConfig,
+    filter: &ListFilter,
+    format: ListFormat,
+) -> Result<()> {
+    let items = inventory_items(config, filter)?;
+    let mut out = String::new();
+    match format {
+        ListFormat::Plain => {
+            for item in &items {
+                let marker = if is_cloned(&item.path) {
+                    ""
+                } else {
+                    " (not cloned)"
+                };
+                let _ = writeln!(
+                    out,
+                    "{}/{}/{} {}{}",
+                    item.repo.host,
+                    item.repo.slug,
+                    item.repo.repo_name,
+                    item.path.display(),
+                    marker
+                );
+            }
+        }
+        ListFormat::Json => {
+            let rows = items
+                .iter()
+                .map(|item| ListRow {
+                    host: &item.repo.host,
+                    slug: &item.repo.slug,
+                    name: &item.repo.repo_name,
+                    path: item.path.display().to_string(),
+                    cloned: is_cloned(&item.path),
+                    watched: item.watched,
+                    metadata: &item.metadata,
+                })
+                .collect::<Vec<_>>();
+            out = serde_json::to_string_pretty(&rows).context("serializing inventory")?;
+            out.push('\n');
+        }
+        ListFormat::Tsv => {
+            out.push_str(
+                "host\tslug\tname\tpath\tcloned\twatched\tdefault_branch\tweb_url\tssh_url\n",
+            );
+            for item in &items {
+                let columns = [
+                    item.repo.host.clone(),
+                    item.repo.slug.clone(),
+                    item.repo.repo_name.clone(),
+                    item.path.display().to_string(),
+                    is_cloned(&item.path).to_string(),
+                    item.watched.to_string(),
+                    item.metadata.default_branch.clone().unwrap_or_default(),
+                    item.metadata.web_url.clone().unwrap_or_default(),
+                    item.metadata.ssh_url.clone().unwrap_or_default(),
+                ];
+                let _ = writeln!(out, "{}", columns.join("\t"));
+            }
+        }
+    }
+
+    // a reader like `head` may stop early; that is not an error
+    match io::stdout().lock().write_all(out.as_bytes()) {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+pub fn view_projects(git: &RealGit, config: &RealGitsConfig, filter: &ListFilter) {
     loop {
-        let inventory_map = config.get_inventory().unwrap();
+        let items = inventory_items(config, filter).unwrap();
 
         let options = SkimOptionsBuilder::default()
             .prompt("Select an option > ".parse().unwrap()) // Set a custom prompt
             .height("50%".parse().unwrap()) // Restrict height (optional)
-            .multi(false) // Disable multi-select
+            .multi(true) // tab ticks repos for a bulk action
+            .preview(Some(String::new())) // items render their own preview
+            .preview_window("right:50%".to_string())
             .build()
             .unwrap();
 
         // 3) Feed items
         let (tx, rx): (SkimItemSender, SkimItemReceiver) = skim::prelude::unbounded();
-        for (host, groups) in &inventory_map {
-            for (slug, projects) in groups {
-                for project in projects {
-                    // no need to clone slug/project; Display handles &String
-                    let line = format!("{host} {slug}/{project}");
-                    // String implements SkimItem, so Arc<String> works
-                    if tx.send(Arc::new(line)).is_err() {
-                        // receiver gone; stop sending
-                        break;
-                    }
-                }
+        for item in items {
+            if tx.send(Arc::new(item)).is_err() {
+                // receiver gone; stop sending
+                break;
             }
         }
         drop(tx); // tell skim there’s no more input
 
         // 4) Run skim and read selection
         let out = Skim::run_with(&options, Some(rx));
-        // let command = Skim::run_with(&options, Some(rx));
         let out = out
             .ok_or_else(|| anyhow::anyhow!("No skim output (user aborted?)"))
             .unwrap();
@@ -75,48 +426,173 @@ pub fn view_projects(git: &RealGit, config: &RealGitsConfig) {
             println!("received escape code. exiting");
             std::process::exit(0);
         }
-        let repo: anyhow::Result<GitRepo> = pick_repo(out);
+        let items = pick_repos(&out);
+        if items.is_empty() {
+            println!("received escape code. exiting");
+            std::process::exit(0);
+        }
 
-        let repo = match repo {
-            Ok(r) => r,
-            Err(_) => {
-                println!("received escape code. exiting");
-                std::process::exit(0);
-            }
+        let action = match run_repo_actions(&items) {
+            Ok(action) => action,
+            Err(_) => continue,
         };
+        if let Err(e) = run_bulk_action(action, &items, git, config) {
+            println!("{:#}", e);
+        }
+    }
+}
 
-        let action = run_repo_actions();
-        match action {
-            Ok(action) => match action.as_str() {
-                "Remote" => {
-                    let url = format!("https://{}/{}/{}", repo.host, repo.slug, repo.repo_name);
-                    open_url(&url).unwrap();
-                    println!("Opened: {url}");
+/// Applies one action to every picked repo. Clone and pull run in parallel
+/// and end with the usual per-repo report.
+fn run_bulk_action(
+    action: ProjectOptions,
+    items: &[&InventoryItem],
+    git: &RealGit,
+    config: &RealGitsConfig,
+) -> Result<()> {
+    let repos = items
+        .iter()
+        .map(|item| item.repo.clone())
+        .collect::<Vec<_>>();
+    match action {
+        ProjectOptions::Remote => open_urls(items, InventoryItem::web_url)?,
+        ProjectOptions::MergeRequests => open_urls(items, InventoryItem::merge_requests_url)?,
+        ProjectOptions::Pipelines => open_urls(items, InventoryItem::pipelines_url)?,
+        ProjectOptions::CopyUrl => {
+            for item in items {
+                println!("{}", item.source.url);
+            }
+        }
+        ProjectOptions::Cd => {
+            // wrapper.sh follows the `cd` line once gits exits
+            println!("cd {}", only_clone(items)?.display());
+            std::process::exit(0);
+        }
+        ProjectOptions::Shell => {
+            let path = only_clone(items)?;
+            let shell = std::env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+            println!(
+                "opening {} in {}; exit it to get back to the list",
+                shell,
+                path.display()
+            );
+            Command::new(&shell)
+                .current_dir(path)
+                .status()
+                .with_context(|| format!("failed to start {}", shell))?;
+        }
+        ProjectOptions::Editor => {
+            let path = only_clone(items)?;
+            let editor = std::env::var("VISUAL")
+                .or_else(|_| std::env::var("EDITOR"))
+                .unwrap_or_else(|_| "vi".to_string());
+            // $EDITOR may carry flags, like "code --wait"
+            let mut words = editor.split_whitespace();
+            let program = words.next().context("$EDITOR is empty")?;
+            Command::new(program)
+                .args(words)
+                .arg(path)
+                .status()
+                .with_context(|| format!("failed to start {}", editor))?;
+        }
+        ProjectOptions::Log => {
+            let path = only_clone(items)?;
+            println!("{}", git.at(path).log(LOG_LINES)?);
+        }
+        ProjectOptions::Clone => {
+            let outcomes = bulk::run_parallel(items, DEFAULT_JOBS, |item| {
+                if is_cloned(&item.path) {
+                    return Outcome::Skipped("already cloned".to_string());
                 }
-                "Clone" => {
-                    println!("trying to CD!!");
-                    git.clone_repo(&repo);
-                    config.add_to_inventory(&repo).unwrap();
+                match git.clone_repo(&item.source, &item.path) {
+                    Ok(()) => Outcome::Cloned,
+                    Err(e) => Outcome::Failed(format!("{:#}", e)),
                 }
-                other => panic!("unknown action: {other}"),
-            },
-            Err(_) => continue,
+            });
+            print_report(&repos, outcomes);
+            // a single clone is something to jump into; wrapper.sh follows the `cd` line
+            if let [item] = items {
+                if is_cloned(&item.path) {
+                    println!("cd {}", item.path.display());
+                }
+            }
+        }
+        ProjectOptions::Pull => {
+            let outcomes = bulk::run_parallel(items, DEFAULT_JOBS, |item| {
+                if !is_cloned(&item.path) {
+                    return Outcome::Skipped("not cloned".to_string());
+                }
+                bulk::pull_guarded(&git.at(&item.path), false)
+            });
+            print_report(&repos, outcomes);
+        }
+        ProjectOptions::Watch => {
+            config.add_watched(&repos)?;
+            println!(
+                "sync-watched now keeps {} more repos up to date",
+                repos.len()
+            );
+        }
+        ProjectOptions::Forget => {
+            let mut by_host: BTreeMap<&str, Vec<GitRepo>> = BTreeMap::new();
+            for repo in &repos {
+                by_host.entry(&repo.host).or_default().push(repo.clone());
+            }
+            for (host, host_repos) in by_host {
+                config.remove_from_inventory(host, &host_repos)?;
+            }
+            println!(
+                "removed {} repos from the inventory; `gits sync --full` adds them back",
+                repos.len()
+            );
         }
     }
+    Ok(())
+}
+
+fn open_urls(items: &[&InventoryItem], url: fn(&InventoryItem) -> String) -> Result<()> {
+    for item in items {
+        let url = url(item);
+        open_url(&url)?;
+        println!("Opened: {url}");
+    }
+    Ok(())
 }
 
-fn run_repo_actions() -> Result<String> {
+/// The clone to work in for actions that only make sense for one repo.
+fn only_clone<'a>(items: &[&'a InventoryItem]) -> Result<&'a Path> {
+    match items {
+        [item] if is_cloned(&item.path) => Ok(&item.path),
+        [item] => bail!("{} is not cloned", item.text()),
+        _ => bail!("pick a single repo for this action"),
+    }
+}
+
+fn print_report(repos: &[GitRepo], outcomes: Vec<Outcome>) {
+    let mut report = Report::new();
+    repos
+        .iter()
+        .zip(outcomes)
+        .for_each(|(repo, outcome)| report.record_repo(repo, outcome));
+    print!("{}", report);
+}
+
+fn run_repo_actions(items: &[&InventoryItem]) -> Result<ProjectOptions> {
     // 1) Build a small command palette
+    let prompt = if items.len() == 1 {
+        "Action > ".to_string()
+    } else {
+        format!("Action for {} repos > ", items.len())
+    };
     let options = SkimOptionsBuilder::default()
-        .prompt("Action > ".to_string())
+        .prompt(prompt)
         .height("40%".to_string())
         .multi(false)
         .build()?;
 
     let (tx, rx): (SkimItemSender, SkimItemReceiver) = unbounded();
-    for label in ["Remote", "Clone"] {
-        // Arc<String> implements SkimItem
-        if tx.send(Arc::new(label)).is_err() {
+    for option in ProjectOptions::available(items) {
+        if tx.send(Arc::new(option)).is_err() {
             bail!("failed to send skim item");
         }
     }
@@ -134,13 +610,15 @@ fn run_repo_actions() -> Result<String> {
         return Err(anyhow::anyhow!("No item selected"));
     }
 
-    let s = item.output();
-
-    Ok(s.to_string())
+    (**item)
+        .as_any()
+        .downcast_ref::<ProjectOptions>()
+        .copied()
+        .ok_or_else(|| anyhow!("unknown action: {}", item.output()))
 }
 
 // Cross-platform URL opener `webbrowser` crate is option
-fn open_url(url: &str) -> Result<()> {
+pub fn open_url(url: &str) -> Result<()> {
     #[cfg(target_os = "macos")]
     {
         Command::new("open")
@@ -1,30 +1,194 @@
+mod bulk;
 mod command_executor;
+mod commit;
 mod config;
+mod dashboard;
 mod git;
 mod github;
 mod gitlab;
 mod list;
+mod merge_request;
+mod prune;
+mod report;
+mod watch;
 
 use crate::command_executor::DebugCommandExecutor;
 use crate::command_executor::RealCommandExecutor;
+use crate::commit::{build_message, BulkCommit, CommitType, MessageOptions, Staging};
 use crate::config::{GitsConfig, RealGitsConfig};
+use crate::dashboard::{Dashboard, DashboardRow};
 
-use crate::git::{project_to_repo, Git, HostKind, RealGit};
-use crate::github::get_watched_github_projects;
+use crate::git::{
+    is_cloned, project_to_repo, Git, HostKind, Integration, PushOutcome, RealGit, RemoteProject,
+    RemoteSettings, TrunkHint,
+};
+use crate::github::{get_watched_github_projects, legacy_inventory_host};
 use crate::gitlab::get_all_gitlab_projects;
+use crate::merge_request::{open_merge_request, MergeRequestOptions};
+use crate::report::{Outcome, Report};
+use anyhow::Context;
 use chrono::{DateTime, Utc};
 use clap::{Args, Parser, Subcommand};
 use dotenv::dotenv;
-use skim::SkimItem;
-use std::io::Write;
+use std::io::IsTerminal;
 use std::path::Path;
 
 #[derive(Args, Debug)]
-struct CommitMessage {
+struct CommitArgs {
+    /// catch up with trunk by rebasing, whatever the remote's `integrate` says
+    #[arg(long, action, conflicts_with = "merge")]
+    rebase: bool,
+    /// catch up with trunk by merging, whatever the remote's `integrate` says
+    #[arg(long, action)]
+    merge: bool,
+    /// stage every change first, untracked files included
+    #[arg(short, long, action, conflicts_with = "pick")]
+    all: bool,
+    /// stage what matches this pathspec first; repeatable
+    #[arg(short, long, value_name = "PATHSPEC")]
+    path: Vec<String>,
+    /// pick the files to stage (tab ticks several)
+    #[arg(long, action)]
+    pick: bool,
+    /// write a conventional commit: `<type>(<scope>): <message>`, with the ticket from the branch name
+    #[arg(short = 't', long = "type", value_enum)]
+    commit_type: Option<CommitType>,
+    /// the conventional commit scope, needs --type
+    #[arg(short, long, requires = "commit_type")]
+    scope: Option<String>,
+    /// commit the same change on a new branch in every inventoried clone matching these
+    /// groups, "slug/repo" paths or globs (comma separated) that has changes
+    #[arg(long, value_name = "FILTER", value_delimiter = ',', requires = "branch", conflicts_with_all = ["pick", "all", "force_with_lease"])]
+    repos: Vec<String>,
+    /// with --repos, the branch to commit on in every repo
+    #[arg(short, long, requires = "repos")]
+    branch: Option<String>,
+    /// with --repos, open the page that creates a merge request for each pushed branch
+    #[arg(long, action, requires = "repos", conflicts_with = "no_push")]
+    open_mr: bool,
+    /// after pushing, open a merge request (pull request on GitHub) into trunk, titled after the commit
+    #[arg(long, action, conflicts_with_all = ["no_push", "open_mr"])]
+    mr: bool,
+    #[command(flatten)]
+    merge_request: MergeRequestArgs,
+    /// with --repos, how many repos to commit in at the same time
+    #[arg(short, long, default_value_t = bulk::DEFAULT_JOBS)]
+    jobs: usize,
+    /// commit only, push later
+    #[arg(long, action, conflicts_with = "force_with_lease")]
+    no_push: bool,
+    /// push with --force-with-lease, e.g. after amending what was already pushed
+    #[arg(long, action)]
+    force_with_lease: bool,
     #[clap(trailing_var_arg = true)]
     commit_message: Vec<String>,
 }
 
+#[derive(Args, Debug)]
+struct MergeRequestArgs {
+    /// open the merge request as a draft
+    #[arg(long, action)]
+    draft: bool,
+    /// assign the merge request to this username; repeatable
+    #[arg(long = "assignee", value_name = "USERNAME")]
+    assignees: Vec<String>,
+    /// label the merge request; repeatable or comma separated
+    #[arg(long = "label", value_name = "LABEL", value_delimiter = ',')]
+    labels: Vec<String>,
+}
+
+impl MergeRequestArgs {
+    fn options(&self, dryrun: bool) -> MergeRequestOptions {
+        MergeRequestOptions {
+            draft: self.draft,
+            assignees: self.assignees.clone(),
+            labels: self.labels.clone(),
+            dryrun,
+        }
+    }
+
+    fn is_set(&self) -> bool {
+        self.draft || !self.assignees.is_empty() || !self.labels.is_empty()
+    }
+}
+
+impl CommitArgs {
+    /// The catch-up strategy asked for on the command line, if any.
+    fn integration(&self) -> Option<Integration> {
+        if self.rebase {
+            Some(Integration::Rebase)
+        } else if self.merge {
+            Some(Integration::Merge)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct StatusArgs {
+    /// fetch every cloned repo from the inventory and print a one-line-per-repo dashboard
+    #[arg(short, long, action)]
+    all: bool,
+    /// with --all, only show repos with uncommitted changes
+    #[arg(long, action, requires = "all")]
+    dirty: bool,
+    /// with --all, only show repos behind their upstream or trunk
+    #[arg(long, action, requires = "all")]
+    behind: bool,
+    /// how many repos to fetch at the same time
+    #[arg(short, long, default_value_t = bulk::DEFAULT_JOBS)]
+    jobs: usize,
+}
+
+#[derive(Args, Debug)]
+struct SyncArgs {
+    /// ignore the last sync time and fetch every project again
+    #[arg(long, action)]
+    full: bool,
+    /// drop projects that were deleted or moved upstream from the inventory, and archived ones with `skip_archived` (implies --full)
+    #[arg(long, action)]
+    prune: bool,
+    /// with --prune, move local clones of pruned projects to the remote's archive_directory
+    #[arg(long, action, requires = "prune")]
+    archive_orphans: bool,
+}
+
+#[derive(Args, Debug)]
+struct ListArgs {
+    /// print the inventory in this format instead of opening the picker
+    #[arg(short, long, value_enum)]
+    format: Option<list::ListFormat>,
+    /// only this remote
+    #[arg(long)]
+    host: Option<String>,
+    /// only this group and the groups below it
+    #[arg(long)]
+    group: Option<String>,
+    /// only repos with a local clone
+    #[arg(long, action, conflicts_with = "not_cloned")]
+    cloned: bool,
+    /// only repos without a local clone
+    #[arg(long, action)]
+    not_cloned: bool,
+    /// only repos in the watch set
+    #[arg(long, action)]
+    watched: bool,
+}
+
+#[derive(Args, Debug)]
+struct SyncWatchedArgs {
+    /// how many repos to clone or pull at the same time
+    #[arg(short, long, default_value_t = bulk::DEFAULT_JOBS)]
+    jobs: usize,
+    /// stash uncommitted changes before pulling and restore them afterwards instead of skipping the repo
+    #[arg(long, action)]
+    autostash: bool,
+    /// sync the whole inventory, not just watch_groups and watch_projects
+    #[arg(short, long, action)]
+    all: bool,
+}
+
 /// git-sync keeps lots of Git repos up to date with one command.
 /// Point it at a directory (or read from a config), and it will discover repositories, check for uncommitted changes, and run the appropriate Git operations (pull/push/fetch) across them.
 /// It favors safety (dry-run by default, dirty-tree guards) and clarity (one compact report at the end), so you can automate daily updates without surprises.
@@ -33,19 +197,23 @@ struct CommitMessage {
 #[command(version)]
 enum Commands {
     #[command(about = "true status - git fetch and status")]
-    Status,
+    Status(StatusArgs),
     #[command(about = "you probably want to pull first? yeah, we are doing that for you")]
-    Commit(CommitMessage),
+    Commit(CommitArgs),
     #[command(about = "opens the repo in browser")]
     Remote,
+    #[command(
+        about = "opens a merge request (pull request on GitHub) from the current branch into trunk, and the browser on it"
+    )]
+    Mr(MergeRequestArgs),
     #[command(about = "gets all new projects from gitlab and puts in a toml for faster search")]
-    Sync,
+    Sync(SyncArgs),
     #[command(
-        about = "list of all projects gits knows about - so you can remote or clone them directly"
+        about = "list of all projects gits knows about - pick one or several (tab) to open, clone, pull, cd into, edit or watch them"
     )]
-    List,
+    List(ListArgs),
     #[command(about = "git pull on all watched projects")]
-    SyncWatched,
+    SyncWatched(SyncWatchedArgs),
 }
 
 #[derive(Parser)]
@@ -58,11 +226,17 @@ pub struct App {
     // outputs to stdout instead of opening in browser.
     #[arg(short, long, action)]
     output: bool,
+    /// run status/commit/remote against an inventoried repo ("slug/repo" or "host/slug/repo") or a path instead of the current directory
+    #[arg(short = 'C', long, global = true)]
+    repo: Option<String>,
 }
 
 #[tokio::main]
-async fn main() {
+async fn main() -> anyhow::Result<()> {
     let args = App::parse();
+    // Load environment variables from .env file: tokens are read from it by the
+    // API calls, and by the credential helper of https-token clones whenever git runs
+    dotenv().ok();
 
     let git = if args.dryrun {
         println!("running in dryrun mode");
@@ -71,83 +245,457 @@ async fn main() {
         RealGit::new(&RealCommandExecutor)
     };
     let config: RealGitsConfig = GitsConfig::build();
+    let git = match &args.repo {
+        Some(repo) if Path::new(repo).is_dir() => git.at(repo),
+        Some(repo) => {
+            let repo = config
+                .find_repo(repo)?
+                .with_context(|| format!("{} is not a directory or an inventoried repo", repo))?;
+            git.at(config.repo_path(&repo))
+        }
+        None => git,
+    };
     match args.cmd {
-        Commands::Status => {
-            let status = git.status().expect("TODO: panic message");
+        Commands::Status(status_args) if status_args.all => {
+            let remotes = config.get_remotes_config()?;
+            let projects = config
+                .get_projects()?
+                .into_iter()
+                .map(|project| {
+                    let path = remotes.repo_path(&project.repo);
+                    (project, path)
+                })
+                .filter(|(_, path)| is_cloned(path))
+                .collect::<Vec<_>>();
+            let rows = bulk::run_parallel(&projects, status_args.jobs, |(project, path)| {
+                let git = git
+                    .at(path)
+                    .with_trunk_hint(remotes.trunk_hint(&project.repo, &project.metadata));
+                let status = git
+                    .fetch()
+                    .and_then(|_| Ok((git.branch_status()?, git.worktree_status()?)))
+                    .map_err(|e| format!("{:#}", e));
+                DashboardRow::new(&project.repo, status)
+            });
+            let rows = rows
+                .into_iter()
+                .filter(|row| {
+                    let filtered = status_args.dirty || status_args.behind;
+                    !filtered
+                        || (status_args.dirty && row.is_dirty())
+                        || (status_args.behind && row.is_behind())
+                })
+                .collect();
+            print!("{}", Dashboard::new(rows));
+        }
+        Commands::Status(_) => {
+            let status = git.status()?;
             println!("{}", status)
         }
-        Commands::Commit(message) => {
-            git.commit(message.commit_message.join(" ").as_str())
-                .expect("TODO: panic message");
-            git.push();
-            println!("commit")
+        Commands::Commit(commit_args) => {
+            let message_options = MessageOptions {
+                commit_type: commit_args.commit_type,
+                scope: commit_args.scope.clone(),
+                conventional: false,
+            };
+            if commit_args.merge_request.is_set() && !commit_args.mr {
+                anyhow::bail!("--draft, --assignee and --label need --mr");
+            }
+            if let Some(branch) = &commit_args.branch {
+                let bulk = BulkCommit {
+                    repos: commit_args.repos.clone(),
+                    branch: branch.clone(),
+                    summary: commit_args.commit_message.join(" "),
+                    message: message_options,
+                    paths: commit_args.path.clone(),
+                    integration: commit_args.integration(),
+                    push: !commit_args.no_push,
+                    open_merge_requests: commit_args.open_mr,
+                    merge_request: commit_args
+                        .mr
+                        .then(|| commit_args.merge_request.options(args.dryrun)),
+                    jobs: commit_args.jobs,
+                };
+                let report = commit::commit_repos(&git, &config, &bulk).await?;
+                print!("{}", report);
+                std::process::exit(report.exit_code());
+            }
+            let settings = remote_settings(&config, &git);
+            let integration = commit_args.integration().unwrap_or_else(|| {
+                settings
+                    .as_ref()
+                    .map(|settings| settings.integrate)
+                    .unwrap_or_default()
+            });
+            let message_options = MessageOptions {
+                conventional: settings.is_some_and(|settings| settings.conventional_commits),
+                ..message_options
+            };
+            let message = build_message(
+                &commit_args.commit_message.join(" "),
+                &message_options,
+                || git.current_branch().ok(),
+            )?;
+            let staging = Staging {
+                all: commit_args.all,
+                paths: commit_args.path.clone(),
+                pick: commit_args.pick,
+            };
+            commit::stage(&git, &staging)?;
+            let hint = trunk_hint(&config, &git);
+            let git = git.with_trunk_hint(hint);
+            // a refused commit returns here, so nothing half-done gets pushed
+            git.commit(&message, integration)?;
+            if commit_args.no_push {
+                println!("Not pushing (--no-push)");
+            } else {
+                push(&git, commit_args.force_with_lease, integration)?;
+                if commit_args.mr {
+                    let options = commit_args.merge_request.options(args.dryrun);
+                    merge_request(&config, &git, &options, args.output).await?;
+                }
+            }
+        }
+        Commands::Mr(merge_request_args) => {
+            let hint = trunk_hint(&config, &git);
+            let git = git.with_trunk_hint(hint);
+            merge_request(
+                &config,
+                &git,
+                &merge_request_args.options(args.dryrun),
+                args.output,
+            )
+            .await?;
         }
         Commands::Remote => {
             if args.output {
-                git.get_remote_url();
+                println!("{}", git.get_remote_url()?);
             } else {
-                git.remote();
+                git.remote()?;
             }
         }
-        Commands::SyncWatched => {
-            config.get_repos().iter().for_each(|repo| {
-                let fetch_head_path = dirs::home_dir().unwrap().join(format!(
-                    "{}/{}/{}/.git",
-                    repo.host, repo.slug, repo.repo_name
-                ));
+        Commands::SyncWatched(sync_args) => {
+            let remotes = config.get_remotes_config()?;
+            let projects = config
+                .get_projects()?
+                .into_iter()
+                .filter(|project| sync_args.all || remotes.is_watched(&project.repo))
+                .collect::<Vec<_>>();
+            if projects.is_empty() {
+                println!("no watched repos - set watch_groups or watch_projects for a remote, or pass --all");
+                return Ok(());
+            }
+            println!("syncing {} repos...", projects.len());
+            let outcomes = bulk::run_parallel(&projects, sync_args.jobs, |project| {
+                let path = remotes.repo_path(&project.repo);
                 // maybe check if dir exists and delete if not a repo? idk
-                if !Path::new(&fetch_head_path).is_dir() {
-                    println!("cloning {:?}", repo);
-                    git.clone_repo(&repo);
+                if !is_cloned(&path) {
+                    let source = remotes.clone_source(&project.repo, &project.metadata);
+                    match git.clone_repo(&source, &path) {
+                        Ok(()) => Outcome::Cloned,
+                        Err(e) => Outcome::Failed(format!("{:#}", e)),
+                    }
+                } else {
+                    bulk::pull_guarded(&git.at(path), sync_args.autostash)
                 }
             });
+
+            let mut report = Report::new();
+            projects
+                .iter()
+                .zip(outcomes)
+                .for_each(|(project, outcome)| report.record_repo(&project.repo, outcome));
+            print!("{}", report);
+            std::process::exit(report.exit_code());
         }
-        Commands::Sync => {
-            dotenv().ok(); // Load environment variables from .env file
+        Commands::Sync(sync_args) => {
             let remotes = config.get_remotes_config().unwrap();
-            let mut repos = Vec::new();
+            let mut report = Report::new();
 
             for (host, remote_settings) in remotes.remotes {
+                // projects created while we page through the API are picked up next time
+                let started = Utc::now();
+                // pruning needs the complete listing to tell what is gone
+                let last_pull = if sync_args.full || sync_args.prune {
+                    DateTime::<Utc>::MIN_UTC
+                } else {
+                    match config.get_last_pull(&host) {
+                        Ok(last_pull) => last_pull,
+                        Err(e) => {
+                            report.record(host, Outcome::Failed(format!("{:#}", e)));
+                            continue;
+                        }
+                    }
+                };
+                let mut repos = Vec::new();
+                let mut errors = Vec::new();
                 if remote_settings.host_kind == HostKind::GitLab {
                     let response = get_all_gitlab_projects(
-                        &*remote_settings.api_url,
-                        &*config.get_private_token(host.clone()),
-                        &remote_settings
-                            .last_pull
-                            .parse::<DateTime<Utc>>()
-                            .expect("failed to parse json created_at"),
+                        &remote_settings.api_url,
+                        &config.get_private_token(host.clone()),
+                        &last_pull,
                     )
                     .await;
-                    
-                    repos.push(project_to_repo(response.unwrap()))
+
+                    match response {
+                        Ok(projects) => {
+                            for project in project_to_repo(projects) {
+                                match project {
+                                    Ok(project) => repos.push(project),
+                                    // one odd url must not hold back the watermark or pruning for good
+                                    Err(e) => eprintln!(
+                                        "warning: skipping a project of {}: {:#}",
+                                        host, e
+                                    ),
+                                }
+                            }
+                        }
+                        Err(e) => errors.push(e.to_string()),
+                    }
                 }
                 if remote_settings.host_kind == HostKind::GitHub {
-                    for username in remote_settings.watch_groups {
+                    let legacy_host = legacy_inventory_host(&remote_settings.api_url);
+                    match config.rename_inventory(&legacy_host, &host) {
+                        Ok(true) => println!(
+                            "Renamed the {} inventory to {}.toml, after its remote",
+                            legacy_host, host
+                        ),
+                        Ok(false) => {}
+                        Err(e) => errors.push(format!("{:#}", e)),
+                    }
+                    for username in remote_settings.watch_groups.clone() {
                         let response = get_watched_github_projects(
-                            &*remote_settings.api_url,
-                            &*config.get_private_token(host.clone()),
-                            &remote_settings
-                                .last_pull
-                                .parse::<DateTime<Utc>>()
-                                .expect("failed to parse json created_at"),
+                            &remote_settings.api_url,
+                            &config.get_private_token(host.clone()),
+                            &last_pull,
                             username,
-                            host.clone()
+                            host.clone(),
                         )
-                            .await;
-                        repos.push(response.unwrap())
-                    }     
+                        .await;
+                        match response {
+                            Ok(user_repos) => repos.extend(user_repos),
+                            Err(e) => errors.push(format!("{:#}", e)),
+                        }
+                    }
+                }
+
+                let indexed = repos
+                    .iter()
+                    .filter(|project| !(remote_settings.skip_archived && project.metadata.archived))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if let Err(e) = config.upsert_inventory(&host, &indexed) {
+                    errors.push(format!("{:#}", e));
+                }
+                if errors.is_empty() && sync_args.prune {
+                    if let Err(e) = prune_host(
+                        &config,
+                        &host,
+                        &remote_settings,
+                        &repos,
+                        sync_args.archive_orphans,
+                        &mut report,
+                    )
+                    .await
+                    {
+                        errors.push(format!("{:#}", e));
+                    }
+                }
+                if errors.is_empty() {
+                    if let Err(e) = config.set_last_pull(&host, started) {
+                        errors.push(format!("{:#}", e));
                     }
-                   
+                }
+                if errors.is_empty() {
+                    report.record(host, Outcome::Synced(repos.len()));
+                } else {
+                    report.record(host, Outcome::Failed(errors.join("\n")));
+                }
             }
-            let repos = repos.iter().flatten().collect::<Vec<_>>();
-            
+            print!("{}", report);
+            std::process::exit(report.exit_code());
+        }
+        Commands::List(list_args) => {
+            let filter = list::ListFilter {
+                host: list_args.host,
+                group: list_args.group,
+                cloned: match (list_args.cloned, list_args.not_cloned) {
+                    (true, _) => Some(true),
+                    (_, true) => Some(false),
+                    _ => None,
+                },
+                watched: list_args.watched,
+            };
+            match list_args.format {
+                Some(format) => list::print_projects(&config, &filter, format)?,
+                None => list::view_projects(&git, &config, &filter),
+            }
+        }
+    }
+    Ok(())
+}
 
-            repos
-                .iter()
-                .for_each(|repo| config.add_to_inventory(repo).unwrap());
+/// The project the repo `git` works in, named the way config.toml and the inventory name it.
+fn current_project(config: &RealGitsConfig, git: &impl Git) -> Option<RemoteProject> {
+    let clone = git.toplevel().ok()?;
+    config.resolve_project(git.origin().ok().as_ref(), &clone)
+}
+
+/// What config.toml and the inventory say about the trunk of the repo `git` works in.
+fn trunk_hint(config: &RealGitsConfig, git: &impl Git) -> TrunkHint {
+    let (Some(project), Ok(remotes)) = (current_project(config, git), config.get_remotes_config())
+    else {
+        return TrunkHint::default();
+    };
+    remotes.trunk_hint(&project.repo, &project.metadata)
+}
+
+/// Pushes HEAD, and when origin has moved on, offers to catch up with it and push again.
+/// When HEAD was rebased after it was pushed it offers a force push instead.
+fn push(git: &impl Git, force_with_lease: bool, integration: Integration) -> anyhow::Result<()> {
+    let branch = match git.push(force_with_lease)? {
+        PushOutcome::Pushed(report) => {
+            println!("Pushing: {}", report);
+            return Ok(());
         }
-        Commands::List => {
-            list::view_projects(&git, &config);
+        PushOutcome::Rejected { branch } => branch,
+        PushOutcome::Rewritten { branch } => return force_push(git, &branch),
+    };
+    let verb = match integration {
+        Integration::Rebase => "rebase",
+        Integration::Merge => "merge",
+    };
+    let question = format!(
+        "origin/{} has commits this branch does not, {} onto it and push again?",
+        branch, verb
+    );
+    if !confirm(&question)? {
+        anyhow::bail!(
+            "push rejected, the commit is only local. Run `git {} origin/{}` and push again",
+            verb,
+            branch
+        );
+    }
+    git.catch_up(&branch, integration)?;
+    match git.push(force_with_lease)? {
+        PushOutcome::Pushed(report) => println!("Pushing: {}", report),
+        PushOutcome::Rejected { .. } | PushOutcome::Rewritten { .. } => {
+            anyhow::bail!(
+                "push rejected again, origin/{} moved on in the meantime",
+                branch
+            )
         }
     }
+    Ok(())
+}
+
+/// Offers to overwrite origin/{branch} with HEAD, which holds the same commits rebased.
+fn force_push(git: &impl Git, branch: &str) -> anyhow::Result<()> {
+    let question = format!(
+        "origin/{} has this branch's commits from before they were rebased, overwrite it with --force-with-lease?",
+        branch
+    );
+    if !confirm(&question)? {
+        anyhow::bail!(
+            "push rejected, the commit is only local. This branch was rebased after it was pushed: \
+             if nobody else works on origin/{}, run `git push --force-with-lease`",
+            branch
+        );
+    }
+    match git.push(true)? {
+        PushOutcome::Pushed(report) => println!("Pushing: {}", report),
+        PushOutcome::Rejected { .. } | PushOutcome::Rewritten { .. } => {
+            anyhow::bail!(
+                "push rejected again, origin/{} moved on in the meantime",
+                branch
+            )
+        }
+    }
+    Ok(())
+}
+
+/// Opens a merge request for the branch `git` has checked out, then prints its
+/// url and, unless only output was asked for, opens it in the browser.
+async fn merge_request(
+    config: &RealGitsConfig,
+    git: &impl Git,
+    options: &MergeRequestOptions,
+    output_only: bool,
+) -> anyhow::Result<()> {
+    let project = current_project(config, git).with_context(|| {
+        format!(
+            "no remote in config.toml matches origin ({}), so gits has no api url or token for it",
+            git.get_remote_url().unwrap_or_else(|e| format!("{:#}", e))
+        )
+    })?;
+    let Some(url) = open_merge_request(config, &project.repo, git, options).await? else {
+        return Ok(());
+    };
+    println!("Merge request: {}", url);
+    if !output_only {
+        list::open_url(&url)?;
+    }
+    Ok(())
+}
+
+/// Asks a yes/no question on the terminal; without one the answer is no.
+fn confirm(question: &str) -> anyhow::Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(false);
+    }
+    eprint!("{} [y/N] ", question);
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// The config.toml section of the remote the repo `git` works in, if it has one.
+fn remote_settings(config: &RealGitsConfig, git: &impl Git) -> Option<RemoteSettings> {
+    let project = current_project(config, git)?;
+    config
+        .get_remotes_config()
+        .ok()?
+        .remotes
+        .remove(&project.repo.host)
+}
+
+/// Removes projects that are gone upstream from one host's inventory and
+/// reports each one, along with what happened to its local clone.
+async fn prune_host(
+    config: &RealGitsConfig,
+    host: &str,
+    settings: &RemoteSettings,
+    listed: &[RemoteProject],
+    archive_orphans: bool,
+    report: &mut Report,
+) -> anyhow::Result<()> {
+    let inventory = config.get_host_repos(host)?;
+    let token = config.get_private_token(host.to_string());
+    let stale = prune::find_stale(host, settings, &token, inventory, listed).await?;
+    let stale_repos = stale
+        .iter()
+        .map(|(repo, _)| repo.clone())
+        .collect::<Vec<_>>();
+    config.remove_from_inventory(host, &stale_repos)?;
+
+    for (repo, reason) in stale {
+        let clone = settings.repo_path(&repo);
+        let outcome = if !is_cloned(&clone) {
+            Outcome::Pruned(reason.to_string())
+        } else if archive_orphans {
+            match prune::archive_clone(settings, &repo, &clone) {
+                Ok(archived) => {
+                    Outcome::Pruned(format!("{}, clone moved to {}", reason, archived.display()))
+                }
+                Err(e) => Outcome::Failed(format!(
+                    "{}, but archiving the clone failed: {:#}",
+                    reason, e
+                )),
+            }
+        } else {
+            Outcome::Pruned(format!("{}, orphaned clone at {}", reason, clone.display()))
+        };
+        report.record_repo(&repo, outcome);
+    }
+    Ok(())
 }
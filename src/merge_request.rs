@@ -0,0 +1,89 @@
+use crate::config::{GitsConfig, RealGitsConfig};
+use crate::git::{Git, GitRepo, HostKind};
+use crate::github::create_github_pull_request;
+use crate::gitlab::create_gitlab_merge_request;
+use anyhow::{anyhow, bail, Result};
+
+/// How to open a merge request, on top of what the branch itself says.
+#[derive(Debug, Clone, Default)]
+pub struct MergeRequestOptions {
+    pub draft: bool,
+    /// usernames on the remote
+    pub assignees: Vec<String>,
+    pub labels: Vec<String>,
+    /// print the merge request instead of sending it
+    pub dryrun: bool,
+}
+
+/// A merge request (pull request on GitHub) about to be opened.
+#[derive(Debug, Clone)]
+pub struct MergeRequest {
+    pub source_branch: String,
+    pub target_branch: String,
+    pub title: String,
+    pub description: String,
+    pub draft: bool,
+    pub assignees: Vec<String>,
+    pub labels: Vec<String>,
+}
+
+impl MergeRequest {
+    /// One from the checked out branch into trunk, titled after the last commit.
+    pub fn for_head(git: &impl Git, options: &MergeRequestOptions) -> Result<Self> {
+        let source_branch = git.current_branch()?;
+        let target_branch = git.trunk()?;
+        if source_branch == target_branch {
+            bail!(
+                "{} is the trunk, commit on a branch to open a merge request",
+                source_branch
+            );
+        }
+        let message = git.head_message()?;
+        let (title, description) = message.split_once('\n').unwrap_or((&message, ""));
+        Ok(MergeRequest {
+            source_branch,
+            target_branch,
+            title: title.trim().to_string(),
+            description: description.trim().to_string(),
+            draft: options.draft,
+            assignees: options.assignees.clone(),
+            labels: options.labels.clone(),
+        })
+    }
+}
+
+/// Opens a merge request on `repo` for the branch `git` has checked out,
+/// and returns its web url, or nothing when it was only printed for `dryrun`.
+/// The branch must be pushed. `repo.host` is the name of the remote in
+/// config.toml, see `GitsConfig::resolve_project`.
+pub async fn open_merge_request(
+    config: &RealGitsConfig,
+    repo: &GitRepo,
+    git: &impl Git,
+    options: &MergeRequestOptions,
+) -> Result<Option<String>> {
+    let remotes = config.get_remotes_config()?;
+    let settings = remotes
+        .remotes
+        .get(&repo.host)
+        .ok_or_else(|| anyhow!("config.toml has no remote named {}", repo.host))?;
+    let request = MergeRequest::for_head(git, options)?;
+    if options.dryrun {
+        println!(
+            "not opening a merge request on {} in dryrun mode: {:#?}",
+            settings.api_url, request
+        );
+        return Ok(None);
+    }
+    let token = config.get_private_token(repo.host.clone());
+    let path = format!("{}/{}", repo.slug, repo.repo_name);
+    let url = match settings.host_kind {
+        HostKind::GitLab => {
+            create_gitlab_merge_request(&settings.api_url, &token, &path, &request).await?
+        }
+        HostKind::GitHub => {
+            create_github_pull_request(&settings.api_url, &token, &path, &request).await?
+        }
+    };
+    Ok(Some(url))
+}
@@ -0,0 +1,144 @@
+use crate::config::expand_path;
+use crate::git::{GitRepo, HostKind, RemoteProject, RemoteSettings};
+use crate::github::find_github_repo;
+use crate::gitlab::find_gitlab_project;
+use anyhow::{bail, Context, Result};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Why an inventoried project no longer belongs in the inventory.
+#[derive(Debug, PartialEq)]
+pub enum StaleReason {
+    Deleted,
+    Archived,
+    Moved(GitRepo),
+    /// still exists, but none of the remote's indexed groups list it anymore
+    Unlisted,
+}
+
+impl fmt::Display for StaleReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StaleReason::Deleted => write!(f, "deleted upstream"),
+            StaleReason::Archived => write!(f, "archived upstream"),
+            StaleReason::Moved(to) => write!(f, "moved to {}/{}", to.slug, to.repo_name),
+            StaleReason::Unlisted => write!(f, "no longer listed"),
+        }
+    }
+}
+
+/// Compares one host's inventory with a full listing from its API.
+/// Projects missing from the listing are looked up one by one to tell
+/// a rename or transfer apart from a deletion. Projects are compared by
+/// path alone: the inventory knows the remote by its config name, while
+/// the API reports the host in its urls.
+pub async fn find_stale(
+    host: &str,
+    settings: &RemoteSettings,
+    private_token: &str,
+    inventory: Vec<GitRepo>,
+    listed: &[RemoteProject],
+) -> Result<Vec<(GitRepo, StaleReason)>> {
+    let mut stale = Vec::new();
+    for repo in inventory {
+        let listing = listed
+            .iter()
+            .find(|project| same_path(&project.repo, &repo));
+        match listing {
+            Some(project) if project.metadata.archived && settings.skip_archived => {
+                stale.push((repo, StaleReason::Archived))
+            }
+            Some(_) => continue,
+            None => {
+                let full_name = format!("{}/{}", repo.slug, repo.repo_name);
+                let found = match settings.host_kind {
+                    HostKind::GitLab => {
+                        find_gitlab_project(&settings.api_url, private_token, &full_name).await
+                    }
+                    HostKind::GitHub => {
+                        find_github_repo(
+                            &settings.api_url,
+                            private_token,
+                            &full_name,
+                            host.to_string(),
+                        )
+                        .await
+                    }
+                }
+                .with_context(|| format!("looking up {}", full_name))?;
+                let reason = match found {
+                    None => StaleReason::Deleted,
+                    Some(moved) if !same_path(&moved, &repo) => StaleReason::Moved(GitRepo {
+                        host: host.to_string(),
+                        ..moved
+                    }),
+                    Some(_) => StaleReason::Unlisted,
+                };
+                stale.push((repo, reason));
+            }
+        }
+    }
+    Ok(stale)
+}
+
+fn same_path(a: &GitRepo, b: &GitRepo) -> bool {
+    a.slug == b.slug && a.repo_name == b.repo_name
+}
+
+/// Moves the clone of a pruned project to `{archive_directory}/{slug}/{repo}`.
+pub fn archive_clone(settings: &RemoteSettings, repo: &GitRepo, clone: &Path) -> Result<PathBuf> {
+    let root = match &settings.archive_directory {
+        Some(directory) => expand_path(directory)?,
+        None => expand_path(&settings.project_directory)?.join(".archive"),
+    };
+    let destination = root.join(&repo.slug).join(&repo.repo_name);
+    if destination.exists() {
+        bail!("{} already exists", destination.display());
+    }
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("creating {}", parent.display()))?;
+    }
+    let moving = || format!("moving {} -> {}", clone.display(), destination.display());
+    match fs::rename(clone, &destination) {
+        // the archive is on another filesystem, so the clone has to be copied over
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            if let Err(e) = copy_tree(clone, &destination) {
+                let _ = fs::remove_dir_all(&destination);
+                return Err(e)
+                    .with_context(|| format!("{} (copying across filesystems)", moving()));
+            }
+            fs::remove_dir_all(clone).with_context(|| {
+                format!(
+                    "copied {} to {}, but removing the original failed",
+                    clone.display(),
+                    destination.display()
+                )
+            })?;
+        }
+        result => result.with_context(moving)?,
+    }
+    Ok(destination)
+}
+
+/// Copies a directory tree, keeping symlinks as symlinks.
+fn copy_tree(from: &Path, to: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(from)?;
+    if metadata.is_symlink() {
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(fs::read_link(from)?, to)?;
+        #[cfg(not(unix))]
+        fs::copy(from, to)?;
+    } else if metadata.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_tree(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        fs::set_permissions(to, metadata.permissions())?;
+    } else {
+        fs::copy(from, to)?;
+    }
+    Ok(())
+}
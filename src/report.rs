@@ -0,0 +1,179 @@
+use crate::git::{GitRepo, PullOutcome};
+use std::fmt;
+
+/// What happened to one repo (or one remote) during a multi-repo operation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Cloned,
+    FastForwarded,
+    UpToDate,
+    DirtySkipped(String),
+    Diverged,
+    Synced(usize),
+    Pruned(String),
+    Committed(String),
+    Skipped(String),
+    Failed(String),
+}
+
+impl Outcome {
+    fn label(&self) -> &'static str {
+        match self {
+            Outcome::Cloned => "cloned",
+            Outcome::FastForwarded => "fast-forwarded",
+            Outcome::UpToDate => "up to date",
+            Outcome::DirtySkipped(_) => "dirty, skipped",
+            Outcome::Diverged => "diverged",
+            Outcome::Synced(_) => "synced",
+            Outcome::Pruned(_) => "pruned",
+            Outcome::Committed(_) => "committed",
+            Outcome::Skipped(_) => "skipped",
+            Outcome::Failed(_) => "failed",
+        }
+    }
+
+    fn detail(&self) -> String {
+        match self {
+            Outcome::DirtySkipped(reason) => reason.clone(),
+            Outcome::Synced(count) => format!("{} projects", count),
+            Outcome::Pruned(reason) => reason.clone(),
+            Outcome::Committed(detail) => detail.clone(),
+            Outcome::Skipped(reason) => reason.clone(),
+            // the full stderr is printed below the table
+            Outcome::Failed(stderr) => stderr.lines().next().unwrap_or_default().to_string(),
+            _ => String::new(),
+        }
+    }
+}
+
+impl From<PullOutcome> for Outcome {
+    fn from(outcome: PullOutcome) -> Self {
+        match outcome {
+            PullOutcome::FastForwarded => Outcome::FastForwarded,
+            PullOutcome::UpToDate => Outcome::UpToDate,
+            PullOutcome::Diverged => Outcome::Diverged,
+            PullOutcome::Dirty => {
+                Outcome::DirtySkipped("local changes would be overwritten".to_string())
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ReportEntry {
+    pub name: String,
+    pub outcome: Outcome,
+}
+
+/// Collects per-repo outcomes so a bulk command can print one table at the end
+/// instead of interleaving output from every worker.
+#[derive(Debug, Default)]
+pub struct Report {
+    entries: Vec<ReportEntry>,
+}
+
+impl Report {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, name: impl Into<String>, outcome: Outcome) {
+        self.entries.push(ReportEntry {
+            name: name.into(),
+            outcome,
+        });
+    }
+
+    pub fn record_repo(&mut self, repo: &GitRepo, outcome: Outcome) {
+        self.record(
+            format!("{} {}/{}", repo.host, repo.slug, repo.repo_name),
+            outcome,
+        );
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| matches!(entry.outcome, Outcome::Failed(_)))
+    }
+
+    /// Process exit code for the command that produced this report.
+    pub fn exit_code(&self) -> i32 {
+        if self.has_failures() {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.entries.is_empty() {
+            return writeln!(f, "nothing to do");
+        }
+        let name_width = self
+            .entries
+            .iter()
+            .map(|entry| entry.name.len())
+            .max()
+            .unwrap_or_default()
+            .max("REPO".len());
+        let label_width = self
+            .entries
+            .iter()
+            .map(|entry| entry.outcome.label().len())
+            .max()
+            .unwrap_or_default()
+            .max("RESULT".len());
+
+        writeln!(
+            f,
+            "{:name_width$}  {:label_width$}  DETAIL",
+            "REPO", "RESULT"
+        )?;
+        for entry in &self.entries {
+            let row = format!(
+                "{:name_width$}  {:label_width$}  {}",
+                entry.name,
+                entry.outcome.label(),
+                entry.outcome.detail()
+            );
+            writeln!(f, "{}", row.trim_end())?;
+        }
+
+        let failures = self
+            .entries
+            .iter()
+            .filter_map(|entry| match &entry.outcome {
+                Outcome::Failed(stderr) => Some((&entry.name, stderr)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if !failures.is_empty() {
+            writeln!(f, "\nfailures:")?;
+            for (name, stderr) in failures {
+                writeln!(f, "  {}:", name)?;
+                for line in stderr.lines() {
+                    writeln!(f, "    {}", line)?;
+                }
+            }
+        }
+
+        // totals in a stable order, e.g. "3 cloned, 280 up to date, 1 failed"
+        let mut totals: Vec<(&str, usize)> = Vec::new();
+        for entry in &self.entries {
+            let label = entry.outcome.label();
+            match totals.iter_mut().find(|(l, _)| *l == label) {
+                Some((_, count)) => *count += 1,
+                None => totals.push((label, 1)),
+            }
+        }
+        let totals = totals
+            .iter()
+            .map(|(label, count)| format!("{} {}", count, label))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(f, "\n{}", totals)
+    }
+}
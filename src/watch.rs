@@ -0,0 +1,164 @@
+use crate::git::{GitRepo, RemoteSettings};
+use crate::github::AUTHENTICATED_USER;
+use regex::Regex;
+
+/// The repos of one remote that `sync-watched` keeps up to date.
+///
+/// `watch_groups` entries match a group and everything below it,
+/// `watch_projects` entries match one exact "slug/repo" path.
+/// An entry in either list containing `*` or `?` is a glob instead:
+/// `*` stays within one path segment, `**` crosses segments, and a
+/// group glob also matches everything below the groups it matches.
+/// On GitHub the same `watch_groups` entries also say which users and
+/// organizations `sync` indexes.
+#[derive(Debug, Default)]
+pub struct WatchSet {
+    groups: Vec<String>,
+    projects: Vec<String>,
+    patterns: Vec<Regex>,
+}
+
+impl WatchSet {
+    pub fn new(groups: &[String], projects: &[String]) -> Self {
+        let mut watch = WatchSet::default();
+        // "@me" only tells GitHub sync what to index; the owner's login is not known here
+        for group in groups.iter().filter(|group| *group != AUTHENTICATED_USER) {
+            match glob_to_regex(group, true) {
+                Some(pattern) => watch.patterns.push(pattern),
+                None => watch.groups.push(group.trim_matches('/').to_string()),
+            }
+        }
+        for project in projects {
+            match glob_to_regex(project, false) {
+                Some(pattern) => watch.patterns.push(pattern),
+                None => {
+                    let project = project.trim_matches('/');
+                    let project = project.strip_suffix(".git").unwrap_or(project);
+                    watch.projects.push(project.to_string())
+                }
+            }
+        }
+        watch
+    }
+
+    /// A filter written like watch entries: a group matches everything
+    /// below it, a "slug/repo" path matches that project, and globs work too.
+    pub fn filter(entries: &[String]) -> Self {
+        WatchSet::new(entries, entries)
+    }
+
+    pub fn matches(&self, repo: &GitRepo) -> bool {
+        let path = format!("{}/{}", repo.slug, repo.repo_name);
+        self.projects.contains(&path)
+            || self
+                .groups
+                .iter()
+                .any(|group| path.starts_with(&format!("{}/", group)))
+            || self.patterns.iter().any(|pattern| pattern.is_match(&path))
+    }
+}
+
+impl RemoteSettings {
+    pub fn watch_set(&self) -> WatchSet {
+        WatchSet::new(&self.watch_groups, &self.watch_projects)
+    }
+}
+
+/// None when `glob` has no wildcards and should be matched literally.
+fn glob_to_regex(glob: &str, with_descendants: bool) -> Option<Regex> {
+    if !glob.contains(['*', '?']) {
+        return None;
+    }
+    let mut regex = String::from("^");
+    let mut chars = glob.trim_matches('/').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                regex.push_str(".*");
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    if with_descendants {
+        regex.push_str("(/.*)?");
+    }
+    regex.push('$');
+    Some(Regex::new(&regex).expect("escaped glob is a valid regex"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo(path: &str) -> GitRepo {
+        let (slug, repo_name) = path.rsplit_once('/').unwrap();
+        GitRepo {
+            host: "work".to_string(),
+            slug: slug.to_string(),
+            repo_name: repo_name.to_string(),
+        }
+    }
+
+    fn strings(entries: &[&str]) -> Vec<String> {
+        entries.iter().map(|entry| entry.to_string()).collect()
+    }
+
+    #[test]
+    fn groups_match_everything_below_them() {
+        let watch = WatchSet::new(&strings(&["platform/"]), &[]);
+        assert!(watch.matches(&repo("platform/api")));
+        assert!(watch.matches(&repo("platform/backend/billing")));
+        assert!(!watch.matches(&repo("platform-tools/cli")));
+        assert!(!watch.matches(&repo("other/platform")));
+    }
+
+    #[test]
+    fn projects_match_one_exact_path() {
+        let watch = WatchSet::new(&[], &strings(&["/platform/api.git", "tools/cli"]));
+        assert!(watch.matches(&repo("platform/api")));
+        assert!(watch.matches(&repo("tools/cli")));
+        assert!(!watch.matches(&repo("platform/api-gateway")));
+        assert!(!watch.matches(&repo("platform/api/v2")));
+    }
+
+    #[test]
+    fn only_one_git_suffix_is_dropped() {
+        let watch = WatchSet::new(&[], &strings(&["tools/app.git.git"]));
+        assert!(watch.matches(&repo("tools/app.git")));
+        assert!(!watch.matches(&repo("tools/app")));
+    }
+
+    #[test]
+    fn a_single_star_stays_within_one_segment() {
+        let watch = WatchSet::new(&[], &strings(&["platform/*-service"]));
+        assert!(watch.matches(&repo("platform/billing-service")));
+        assert!(!watch.matches(&repo("platform/backend/billing-service")));
+        assert!(!watch.matches(&repo("platform/billing-service-old")));
+    }
+
+    #[test]
+    fn a_double_star_crosses_segments() {
+        let watch = WatchSet::new(&[], &strings(&["platform/**/deploy"]));
+        assert!(watch.matches(&repo("platform/backend/deploy")));
+        assert!(watch.matches(&repo("platform/backend/billing/deploy")));
+        assert!(!watch.matches(&repo("tools/backend/deploy")));
+    }
+
+    #[test]
+    fn group_globs_match_below_the_groups_they_match() {
+        let watch = WatchSet::new(&strings(&["team-?"]), &[]);
+        assert!(watch.matches(&repo("team-a/app")));
+        assert!(watch.matches(&repo("team-b/backend/api")));
+        assert!(!watch.matches(&repo("team-ab/app")));
+    }
+
+    #[test]
+    fn the_authenticated_user_entry_matches_nothing() {
+        let watch = WatchSet::new(&strings(&[AUTHENTICATED_USER]), &[]);
+        assert!(!watch.matches(&repo(&format!("{}/dotfiles", AUTHENTICATED_USER))));
+        assert!(!watch.matches(&repo("octocat/hello")));
+    }
+}